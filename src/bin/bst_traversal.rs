@@ -0,0 +1,122 @@
+// Traversal throughput of bst1::BSTree, in nodes visited per second: "before" is the
+// LinkedList-stack iterators it used to have (rebuilt here on top of NodeRef, which is what they
+// did with the inner nodes), "after" is BSTree::iter(). std only; run it with
+//     cargo run --release --bin bst_traversal
+use rust_examples::prng::Prng;
+use rust_examples::tree::bst1::{BSTree, NodeRef, Order};
+use std::collections::LinkedList;
+use std::hint::black_box;
+use std::time::Instant;
+
+const N: usize = 1_000_000;
+const ROUNDS: usize = 10;
+
+struct OldPreorderItr<'a, T> {
+    stack: LinkedList<NodeRef<'a, T>>,
+}
+
+impl<'a, T> Iterator for OldPreorderItr<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop_back()?;
+        if let Some(right) = node.right() {
+            self.stack.push_back(right);
+        }
+        if let Some(left) = node.left() {
+            self.stack.push_back(left);
+        }
+        Some(node.value())
+    }
+}
+
+struct OldInorderItr<'a, T> {
+    stack: LinkedList<NodeRef<'a, T>>,
+}
+
+impl<'a, T> OldInorderItr<'a, T> {
+    fn push_till_next(&mut self, mut node: Option<NodeRef<'a, T>>) {
+        while let Some(n) = node {
+            self.stack.push_back(n);
+            node = n.left();
+        }
+    }
+}
+
+impl<'a, T> Iterator for OldInorderItr<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop_back()?;
+        self.push_till_next(node.right());
+        Some(node.value())
+    }
+}
+
+// there were only preorder and inorder iterators; each is returned as its own type, not boxed,
+// so that it's dispatched statically, the same as BSTree::iter();
+fn old_preorder(bst: &BSTree<u64>) -> OldPreorderItr<'_, u64> {
+    let mut stack = LinkedList::new();
+    stack.extend(bst.root());
+    OldPreorderItr { stack }
+}
+
+fn old_inorder(bst: &BSTree<u64>) -> OldInorderItr<'_, u64> {
+    let mut itr = OldInorderItr {
+        stack: LinkedList::new(),
+    };
+    itr.push_till_next(bst.root());
+    itr
+}
+
+// nodes visited per second, over ROUNDS full traversals; the sum keeps the loop from being
+// optimized away;
+fn measure<'a, I, F>(make_itr: F) -> f64
+where
+    I: Iterator<Item = &'a u64>,
+    F: Fn() -> I,
+{
+    let start = Instant::now();
+    let mut visited = 0;
+    for _ in 0..ROUNDS {
+        let mut sum: u64 = 0;
+        for v in make_itr() {
+            sum = sum.wrapping_add(*v);
+            visited += 1;
+        }
+        black_box(sum);
+    }
+    visited as f64 / start.elapsed().as_secs_f64()
+}
+
+fn report(name: &str, bst: &BSTree<u64>) {
+    println!("{} ({} nodes, height {}):", name, N, bst.height());
+    let before = measure(|| old_preorder(bst));
+    let after = measure(|| bst.iter(Order::Pre));
+    print_row(Order::Pre, before, after);
+    let before = measure(|| old_inorder(bst));
+    let after = measure(|| bst.iter(Order::In));
+    print_row(Order::In, before, after);
+}
+
+fn print_row(order: Order, before: f64, after: f64) {
+    println!(
+        "    {:<4} before {:7.1} M nodes/s, after {:7.1} M nodes/s, x{:.2}",
+        format!("{:?}:", order),
+        before / 1e6,
+        after / 1e6,
+        after / before
+    );
+}
+
+fn main() {
+    let mut rng = Prng::new(1);
+    let mut random: BSTree<u64> = BSTree::new();
+    for _ in 0..N {
+        random.insert(rng.next_u64());
+    }
+    report("random insertion", &random);
+
+    let balanced: BSTree<u64> = BSTree::from_sorted((0..N as u64).collect());
+    report("perfectly balanced", &balanced);
+}
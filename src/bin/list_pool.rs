@@ -0,0 +1,82 @@
+// Allocations and throughput of linkedlist1::List against pooled::PooledList on a push/pop churn
+// (a stack that keeps growing to DEPTH and draining back to empty), which is where a node pool
+// pays: after the first round every push of the pooled list reuses a node. Allocations are
+// counted by a global allocator wrapping System. std only; run it with
+//     cargo run --release --bin list_pool
+use rust_examples::list::linkedlist1::List;
+use rust_examples::list::pooled::PooledList;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+struct Counting;
+
+static ALLOCS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const DEPTH: u64 = 1_000;
+const ROUNDS: u64 = 10_000;
+
+// the 2 lists share no trait, so the churn takes their push and pop as fn pointers;
+fn churn<L>(list: &mut L, push: fn(&mut L, u64), pop: fn(&mut L) -> Option<u64>) -> (usize, f64) {
+    let allocs = ALLOCS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    let mut sum: u64 = 0;
+    for round in 0..ROUNDS {
+        for i in 0..DEPTH {
+            push(list, round ^ i);
+        }
+        while let Some(v) = pop(list) {
+            sum = sum.wrapping_add(v);
+        }
+    }
+    black_box(sum);
+    let ops = 2 * DEPTH * ROUNDS;
+    (
+        ALLOCS.load(Ordering::Relaxed) - allocs,
+        ops as f64 / start.elapsed().as_secs_f64(),
+    )
+}
+
+fn report(name: &str, (allocs, ops_per_sec): (usize, f64)) {
+    println!(
+        "    {:<22} {:>10} allocations, {:7.1} M ops/s",
+        name,
+        allocs,
+        ops_per_sec / 1e6
+    );
+}
+
+fn main() {
+    println!("{} rounds of {} pushes then {} pops:", ROUNDS, DEPTH, DEPTH);
+
+    let mut list = List::new();
+    report("List", churn(&mut list, List::push_front, List::pop_front));
+
+    let mut pooled = PooledList::new();
+    report(
+        "PooledList",
+        churn(&mut pooled, PooledList::push_front, PooledList::pop_front),
+    );
+
+    // a pool smaller than DEPTH only saves the part of each round that fits in it;
+    let mut small = PooledList::with_pool_cap(DEPTH as usize / 4);
+    report(
+        "PooledList, cap 1/4",
+        churn(&mut small, PooledList::push_front, PooledList::pop_front),
+    );
+}
@@ -0,0 +1,57 @@
+// Sequential scan throughput of linkedlist1::List against unrolled::UnrolledList, in elements
+// per second. A fresh List tends to get its nodes from the allocator one after the other, i.e.
+// laid out in order in memory, which flatters it; after sort() relinks them by random keys, a
+// scan jumps all over the heap, which is what a list that has lived a while looks like. std only;
+// run it with
+//     cargo run --release --bin list_scan
+use rust_examples::list::linkedlist1::List;
+use rust_examples::list::unrolled::UnrolledList;
+use rust_examples::prng::Prng;
+use std::hint::black_box;
+use std::time::Instant;
+
+const N: usize = 1_000_000;
+const ROUNDS: usize = 20;
+
+// elements visited per second, over ROUNDS full scans; the sum keeps the loop from being
+// optimized away;
+fn measure<'a, I, F>(make_itr: F) -> f64
+where
+    I: Iterator<Item = &'a u64>,
+    F: Fn() -> I,
+{
+    let start = Instant::now();
+    let mut visited = 0;
+    for _ in 0..ROUNDS {
+        let mut sum: u64 = 0;
+        for v in make_itr() {
+            sum = sum.wrapping_add(*v);
+            visited += 1;
+        }
+        black_box(sum);
+    }
+    visited as f64 / start.elapsed().as_secs_f64()
+}
+
+fn main() {
+    let mut rng = Prng::new(1);
+    let vals: Vec<u64> = (0..N).map(|_| rng.next_u64()).collect();
+
+    let mut list: List<u64> = vals.iter().cloned().collect();
+    let unrolled: UnrolledList<u64> = vals.iter().cloned().collect();
+
+    println!("scanning {} elements:", N);
+    let base = measure(|| list.itr());
+    let report = |name: &str, per_sec: f64| {
+        println!(
+            "    {:<24} {:7.1} M elements/s, x{:.2}",
+            name,
+            per_sec / 1e6,
+            per_sec / base
+        )
+    };
+    report("List, fresh", base);
+    list.sort();
+    report("List, after sort()", measure(|| list.itr()));
+    report("UnrolledList", measure(|| unrolled.itr()));
+}
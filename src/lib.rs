@@ -1,3 +1,4 @@
 pub mod list;
+pub mod prng;
 pub mod test_match;
 pub mod tree;
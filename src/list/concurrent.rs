@@ -0,0 +1,621 @@
+use std::marker::PhantomData;
+use std::mem::ManuallyDrop;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+
+// A lock-free stack (Treiber's): the list is linked just like linkedlist1::List, but the head is
+// an AtomicPtr, and push_front()/pop_front() take `&self` and swing the head with
+// compare-and-swap, retrying if another thread got there first.
+//
+// The hard part is freeing a popped node: another thread may have loaded the same head just
+// before, and be about to read its `next`. So memory is reclaimed with hazard pointers: before
+// touching a node, a popping thread publishes its address in a hazard record, and a popped node
+// is only "retired"; retired nodes are freed by scan(), which skips every node that some record
+// still points to. That also rules out ABA: a node can't be freed and reallocated at the same
+// address while a thread still holds it.
+//
+// Both operations are written as small state machines, one atomic step per call to step(): the
+// real push/pop just run them to the end, and the tests below run them interleaved, step by
+// step, in every possible order.
+pub struct ConcurrentStack<T: Send> {
+    head: AtomicPtr<Node<T>>,
+    // hazard records, one per pop in progress; they are reused, and only freed with the stack;
+    records: AtomicPtr<HazardRecord<T>>,
+    // popped nodes waiting to be freed;
+    retired: AtomicPtr<Retired<T>>,
+    retired_count: AtomicUsize,
+    scan_threshold: usize,
+    _owns: PhantomData<T>,
+}
+
+// `next` is written only before the node is published by the CAS in PushOp, so it can be read
+// without synchronization afterwards; `val` is moved out only by the thread whose pop succeeded,
+// and the node is freed without dropping it again.
+struct Node<T> {
+    val: ManuallyDrop<T>,
+    next: *mut Node<T>,
+}
+
+struct HazardRecord<T> {
+    hazard: AtomicPtr<Node<T>>,
+    active: AtomicBool,
+    // written before the record is published, never after;
+    next: *mut HazardRecord<T>,
+}
+
+struct Retired<T> {
+    node: *mut Node<T>,
+    next: *mut Retired<T>,
+}
+
+// scan() once this many nodes are retired; the number of nodes kept back is bounded by this plus
+// the number of hazard records;
+const SCAN_THRESHOLD: usize = 64;
+
+// The values are moved in by one thread and out by another, but never shared, so T: Send is all
+// it takes for both.
+unsafe impl<T: Send> Send for ConcurrentStack<T> {}
+unsafe impl<T: Send> Sync for ConcurrentStack<T> {}
+
+impl<T: Send> ConcurrentStack<T> {
+    pub fn new() -> Self {
+        ConcurrentStack::with_scan_threshold(SCAN_THRESHOLD)
+    }
+
+    fn with_scan_threshold(scan_threshold: usize) -> Self {
+        ConcurrentStack {
+            head: AtomicPtr::new(ptr::null_mut()),
+            records: AtomicPtr::new(ptr::null_mut()),
+            retired: AtomicPtr::new(ptr::null_mut()),
+            retired_count: AtomicUsize::new(0),
+            scan_threshold,
+            _owns: PhantomData,
+        }
+    }
+
+    pub fn push_front(&self, val: T) {
+        let mut op = PushOp::new(self, val);
+        while !op.step() {}
+    }
+
+    pub fn pop_front(&self) -> Option<T> {
+        let mut op = PopOp::new(self);
+        loop {
+            if let Some(result) = op.step() {
+                return result;
+            }
+        }
+    }
+
+    // only a snapshot, other threads may change it right away;
+    pub fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Acquire).is_null()
+    }
+
+    // Take an inactive record, or add a new one to the list (lock-free, like a push).
+    fn acquire_record(&self) -> &HazardRecord<T> {
+        let mut p = self.records.load(Ordering::Acquire);
+        while !p.is_null() {
+            // SAFETY: records are only freed in drop(), which has `&mut self`;
+            let record = unsafe { &*p };
+            if !record.active.load(Ordering::Relaxed)
+                && record
+                    .active
+                    .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+            {
+                return record;
+            }
+            p = record.next;
+        }
+
+        let record = Box::into_raw(Box::new(HazardRecord {
+            hazard: AtomicPtr::new(ptr::null_mut()),
+            active: AtomicBool::new(true),
+            next: ptr::null_mut(),
+        }));
+        let mut head = self.records.load(Ordering::Relaxed);
+        loop {
+            // SAFETY: not published yet, so still only ours;
+            unsafe { (*record).next = head };
+            match self.records.compare_exchange_weak(
+                head,
+                record,
+                Ordering::Release,
+                Ordering::Relaxed,
+            ) {
+                // SAFETY: see above, records live as long as the stack;
+                Ok(_) => return unsafe { &*record },
+                Err(actual) => head = actual,
+            }
+        }
+    }
+
+    // The count goes up before the push (and down after a scan takes the node), so it never
+    // drops below the number of nodes in the list.
+    fn retire(&self, node: *mut Node<T>) {
+        let count = self.retired_count.fetch_add(1, Ordering::Relaxed) + 1;
+        self.push_retired(Box::into_raw(Box::new(Retired {
+            node,
+            next: ptr::null_mut(),
+        })));
+        if count >= self.scan_threshold {
+            self.scan();
+        }
+    }
+
+    fn push_retired(&self, retired: *mut Retired<T>) {
+        let mut head = self.retired.load(Ordering::Relaxed);
+        loop {
+            // SAFETY: `retired` is not published yet;
+            unsafe { (*retired).next = head };
+            match self.retired.compare_exchange_weak(
+                head,
+                retired,
+                Ordering::Release,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return,
+                Err(actual) => head = actual,
+            }
+        }
+    }
+
+    // Take the whole retired list (so concurrent scans get disjoint parts), free the nodes no
+    // record points to, and put the others back for a later scan.
+    fn scan(&self) {
+        let mut list = self.retired.swap(ptr::null_mut(), Ordering::Acquire);
+        let mut hazards: Vec<*mut Node<T>> = Vec::new();
+        let mut p = self.records.load(Ordering::Acquire);
+        while !p.is_null() {
+            // SAFETY: see acquire_record();
+            let record = unsafe { &*p };
+            let hazard = record.hazard.load(Ordering::SeqCst);
+            if !hazard.is_null() {
+                hazards.push(hazard);
+            }
+            p = record.next;
+        }
+
+        while !list.is_null() {
+            // SAFETY: the retired list was taken by the swap above, so it's only ours now;
+            let retired = unsafe { Box::from_raw(list) };
+            list = retired.next;
+            if hazards.contains(&retired.node) {
+                self.push_retired(Box::into_raw(retired));
+            } else {
+                self.retired_count.fetch_sub(1, Ordering::Relaxed);
+                // SAFETY: the node is unreachable from the head, and no thread has it as its
+                // hazard, so no thread can be reading it; its value was moved out already;
+                unsafe { drop(Box::from_raw(retired.node)) };
+            }
+        }
+    }
+}
+
+impl<T: Send> Default for ConcurrentStack<T> {
+    fn default() -> Self {
+        ConcurrentStack::new()
+    }
+}
+
+impl<T: Send> Drop for ConcurrentStack<T> {
+    // `&mut self`: no other thread can be in the middle of an operation, so everything can go;
+    fn drop(&mut self) {
+        let mut p = *self.head.get_mut();
+        while !p.is_null() {
+            // SAFETY: nodes of the stack own their values;
+            let mut node = unsafe { Box::from_raw(p) };
+            p = node.next;
+            unsafe { ManuallyDrop::drop(&mut node.val) };
+        }
+        let mut p = *self.retired.get_mut();
+        while !p.is_null() {
+            // SAFETY: retired nodes are freed without their values, which were popped;
+            let retired = unsafe { Box::from_raw(p) };
+            p = retired.next;
+            unsafe { drop(Box::from_raw(retired.node)) };
+        }
+        let mut p = *self.records.get_mut();
+        while !p.is_null() {
+            let record = unsafe { Box::from_raw(p) };
+            p = record.next;
+        }
+    }
+}
+
+// A push: load the head into the new node's `next`, then CAS the head from that to the new node;
+// if the head moved in between, the CAS fails and returns the new head, so the next try goes
+// straight to the CAS again.
+struct PushOp<'a, T: Send> {
+    stack: &'a ConcurrentStack<T>,
+    node: *mut Node<T>,
+    loaded: bool,
+}
+
+impl<'a, T: Send> PushOp<'a, T> {
+    fn new(stack: &'a ConcurrentStack<T>, val: T) -> Self {
+        let node = Box::into_raw(Box::new(Node {
+            val: ManuallyDrop::new(val),
+            next: ptr::null_mut(),
+        }));
+        PushOp {
+            stack,
+            node,
+            loaded: false,
+        }
+    }
+
+    // one atomic step; true when the node is in;
+    fn step(&mut self) -> bool {
+        // SAFETY: the node is not published until the CAS succeeds, so it's only ours here;
+        unsafe {
+            if !self.loaded {
+                (*self.node).next = self.stack.head.load(Ordering::Relaxed);
+                self.loaded = true;
+                return false;
+            }
+            // Release: whoever loads the new head sees `val` and `next` written;
+            match self.stack.head.compare_exchange(
+                (*self.node).next,
+                self.node,
+                Ordering::Release,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => true,
+                Err(actual) => {
+                    (*self.node).next = actual;
+                    false
+                }
+            }
+        }
+    }
+}
+
+enum PopState<T> {
+    Load,
+    // the head was loaded and published as our hazard;
+    Validate(*mut Node<T>),
+    // ... and it was still the head after that;
+    Cas(*mut Node<T>),
+}
+
+// A pop: load the head and publish it as our hazard, then check that it is still the head (if it
+// was popped and retired in between, a scan may have missed our hazard, so start over); from
+// then on it can't be freed, so reading its `next` is safe, and the CAS from it to `next` decides
+// who gets it.
+//
+// The hazard store and the check, against the CAS and the hazard loads in scan(), are the
+// classic store-then-load pattern on 2 threads, which only SeqCst keeps from being reordered:
+// either the scan sees our hazard, or we see the head has moved.
+struct PopOp<'a, T: Send> {
+    stack: &'a ConcurrentStack<T>,
+    record: &'a HazardRecord<T>,
+    state: PopState<T>,
+}
+
+impl<'a, T: Send> PopOp<'a, T> {
+    fn new(stack: &'a ConcurrentStack<T>) -> Self {
+        PopOp {
+            stack,
+            record: stack.acquire_record(),
+            state: PopState::Load,
+        }
+    }
+
+    // one atomic step; Some(result) when done;
+    fn step(&mut self) -> Option<Option<T>> {
+        let head = &self.stack.head;
+        match self.state {
+            PopState::Load => {
+                let node = head.load(Ordering::Acquire);
+                if node.is_null() {
+                    return Some(None);
+                }
+                self.record.hazard.store(node, Ordering::SeqCst);
+                self.state = PopState::Validate(node);
+            }
+            PopState::Validate(node) => {
+                self.state = if head.load(Ordering::SeqCst) == node {
+                    PopState::Cas(node)
+                } else {
+                    PopState::Load
+                };
+            }
+            PopState::Cas(node) => {
+                // SAFETY: our hazard keeps the node from being freed, and `next` never changes
+                // once the node is published;
+                let next = unsafe { (*node).next };
+                if head
+                    .compare_exchange(node, next, Ordering::SeqCst, Ordering::Relaxed)
+                    .is_err()
+                {
+                    self.state = PopState::Load;
+                    return None;
+                }
+                self.record.hazard.store(ptr::null_mut(), Ordering::Release);
+                // SAFETY: the CAS made the node ours: nobody else takes the value, and other
+                // threads may only read its `next`;
+                let val = unsafe { ManuallyDrop::into_inner(ptr::read(&(*node).val)) };
+                self.stack.retire(node);
+                return Some(Some(val));
+            }
+        }
+        None
+    }
+}
+
+impl<'a, T: Send> Drop for PopOp<'a, T> {
+    fn drop(&mut self) {
+        self.record.hazard.store(ptr::null_mut(), Ordering::Release);
+        self.record.active.store(false, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ConcurrentStack, PopOp, PushOp};
+    use std::collections::BTreeSet;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn basics() {
+        let s: ConcurrentStack<i32> = ConcurrentStack::new();
+        assert!(s.is_empty());
+        assert_eq!(s.pop_front(), None);
+        s.push_front(1);
+        s.push_front(2);
+        s.push_front(3);
+        assert!(!s.is_empty());
+        assert_eq!(s.pop_front(), Some(3));
+        assert_eq!(s.pop_front(), Some(2));
+        s.push_front(4);
+        assert_eq!(s.pop_front(), Some(4));
+        assert_eq!(s.pop_front(), Some(1));
+        assert_eq!(s.pop_front(), None);
+        assert!(s.is_empty());
+    }
+
+    // counts the drops, to check that every value is dropped exactly once, whether it is popped
+    // or left in the stack;
+    struct Counted(u64, Arc<AtomicUsize>);
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.1.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // Every thread pushes its own values and pops as many (not necessarily its own); all the
+    // values must come out exactly once.
+    #[test]
+    fn test_stress() {
+        let threads = 8;
+        let per_thread: u64 = if cfg!(miri) { 50 } else { 20_000 };
+        let drops = Arc::new(AtomicUsize::new(0));
+        let stack = Arc::new(ConcurrentStack::new());
+
+        let handles: Vec<_> = (0..threads)
+            .map(|t| {
+                let stack = Arc::clone(&stack);
+                let drops = Arc::clone(&drops);
+                thread::spawn(move || {
+                    let mut popped = Vec::new();
+                    for i in 0..per_thread {
+                        stack.push_front(Counted(t * per_thread + i, Arc::clone(&drops)));
+                        if i % 2 == 1 {
+                            popped.push(stack.pop_front().unwrap().0);
+                            popped.push(stack.pop_front().unwrap().0);
+                        }
+                    }
+                    popped
+                })
+            })
+            .collect();
+
+        let mut all: Vec<u64> = Vec::new();
+        for h in handles {
+            all.extend(h.join().unwrap());
+        }
+        assert_eq!(drops.load(Ordering::Relaxed), all.len());
+        while let Some(v) = stack.pop_front() {
+            all.push(v.0);
+        }
+        all.sort_unstable();
+        let expect: Vec<u64> = (0..threads * per_thread).collect();
+        assert_eq!(all, expect);
+
+        // leftovers are dropped with the stack;
+        stack.push_front(Counted(0, Arc::clone(&drops)));
+        stack.push_front(Counted(1, Arc::clone(&drops)));
+        drop(stack);
+        assert_eq!(drops.load(Ordering::Relaxed), expect.len() + 2);
+    }
+
+    // One thread only pushes, the others only pop, spinning while the stack is empty.
+    #[test]
+    fn test_stress_producer_consumers() {
+        let consumers = 4;
+        let n: u64 = if cfg!(miri) { 100 } else { 100_000 };
+        let stack = Arc::new(ConcurrentStack::new());
+        let taken = Arc::new(AtomicUsize::new(0));
+
+        let producer = {
+            let stack = Arc::clone(&stack);
+            thread::spawn(move || {
+                for i in 0..n {
+                    stack.push_front(i);
+                }
+            })
+        };
+        let handles: Vec<_> = (0..consumers)
+            .map(|_| {
+                let stack = Arc::clone(&stack);
+                let taken = Arc::clone(&taken);
+                thread::spawn(move || {
+                    let mut sum: u64 = 0;
+                    while taken.load(Ordering::Relaxed) < n as usize {
+                        if let Some(v) = stack.pop_front() {
+                            sum += v;
+                            taken.fetch_add(1, Ordering::Relaxed);
+                        } else {
+                            thread::yield_now();
+                        }
+                    }
+                    sum
+                })
+            })
+            .collect();
+
+        producer.join().unwrap();
+        let sum: u64 = handles.into_iter().map(|h| h.join().unwrap()).sum();
+        assert_eq!(sum, n * (n - 1) / 2);
+        assert!(stack.is_empty());
+    }
+
+    // Hand-written model checking, in the spirit of loom: a few push/pop ops on one stack, run
+    // on a single thread one atomic step at a time (the very step() functions push_front() and
+    // pop_front() run), in every possible interleaving. Each schedule is replayed from scratch;
+    // the scan threshold is 1 so that every pop scans, and a node still held by another pop
+    // must survive the scan (Miri catches it if it doesn't).
+    enum Op<'a> {
+        Push(PushOp<'a, u32>, bool),
+        Pop(PopOp<'a, u32>, Option<Option<u32>>),
+    }
+
+    impl<'a> Op<'a> {
+        fn done(&self) -> bool {
+            match self {
+                Op::Push(_, done) => *done,
+                Op::Pop(_, result) => result.is_some(),
+            }
+        }
+
+        fn step(&mut self) {
+            match self {
+                Op::Push(op, done) => *done = op.step(),
+                Op::Pop(op, result) => *result = op.step(),
+            }
+        }
+    }
+
+    // (what each pop returned, what's left in the stack from the top)
+    type Outcome = (Vec<Option<u32>>, Vec<u32>);
+
+    // Run `schedule` (indexes of the ops to step) from a fresh stack holding `setup` (pushed in
+    // order); returns the ops which are not done, and the outcome once all are done. `ops` has
+    // Some(val) for a push of val, and None for a pop.
+    fn replay(setup: &[u32], ops: &[Option<u32>], schedule: &[usize]) -> (Vec<usize>, Outcome) {
+        let stack = ConcurrentStack::with_scan_threshold(1);
+        for v in setup {
+            stack.push_front(*v);
+        }
+        let mut running: Vec<Op> = ops
+            .iter()
+            .map(|op| match op {
+                Some(v) => Op::Push(PushOp::new(&stack, *v), false),
+                None => Op::Pop(PopOp::new(&stack), None),
+            })
+            .collect();
+        for i in schedule {
+            running[*i].step();
+        }
+
+        let runnable: Vec<usize> = (0..running.len()).filter(|i| !running[*i].done()).collect();
+        let popped = running
+            .iter()
+            .filter_map(|op| match op {
+                Op::Pop(_, result) => Some(result.unwrap_or(None)),
+                _ => None,
+            })
+            .collect();
+        drop(running);
+        let mut left = Vec::new();
+        if runnable.is_empty() {
+            while let Some(v) = stack.pop_front() {
+                left.push(v);
+            }
+        }
+        (runnable, (popped, left))
+    }
+
+    fn explore(
+        setup: &[u32],
+        ops: &[Option<u32>],
+        schedule: &mut Vec<usize>,
+        outcomes: &mut BTreeSet<Outcome>,
+        schedules: &mut usize,
+    ) {
+        let (runnable, outcome) = replay(setup, ops, schedule);
+        if runnable.is_empty() {
+            outcomes.insert(outcome);
+            *schedules += 1;
+            return;
+        }
+        for i in runnable {
+            schedule.push(i);
+            explore(setup, ops, schedule, outcomes, schedules);
+            schedule.pop();
+        }
+    }
+
+    fn all_outcomes(setup: &[u32], ops: &[Option<u32>]) -> (BTreeSet<Outcome>, usize) {
+        let mut outcomes = BTreeSet::new();
+        let mut schedules = 0;
+        explore(setup, ops, &mut Vec::new(), &mut outcomes, &mut schedules);
+        (outcomes, schedules)
+    }
+
+    #[test]
+    fn test_interleavings_pop_pop() {
+        // 2 pops racing for the top: each gets one value, whatever the order of their steps;
+        let (outcomes, schedules) = all_outcomes(&[1, 2], &[None, None]);
+        assert!(schedules > 10);
+        let expect: BTreeSet<Outcome> = vec![
+            (vec![Some(2), Some(1)], vec![]),
+            (vec![Some(1), Some(2)], vec![]),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(outcomes, expect);
+
+        // ... and with one value, exactly one of them gets it;
+        let (outcomes, _) = all_outcomes(&[1], &[None, None]);
+        let expect: BTreeSet<Outcome> =
+            vec![(vec![Some(1), None], vec![]), (vec![None, Some(1)], vec![])]
+                .into_iter()
+                .collect();
+        assert_eq!(outcomes, expect);
+    }
+
+    #[test]
+    fn test_interleavings_push_pop() {
+        // the pop is linearized either before or after the push;
+        let (outcomes, _) = all_outcomes(&[1], &[Some(2), None]);
+        let expect: BTreeSet<Outcome> = vec![(vec![Some(1)], vec![2]), (vec![Some(2)], vec![1])]
+            .into_iter()
+            .collect();
+        assert_eq!(outcomes, expect);
+
+        // 2 pops and a push on 2 values: the values are conserved and each pop gets one, also
+        // when a pop stalls on a node which is popped by the other one meanwhile (its hazard
+        // keeps the node from being freed, and so its address from being reused by the push);
+        let (outcomes, schedules) = all_outcomes(&[1, 2], &[None, Some(3), None]);
+        assert!(schedules > 1000);
+        for (popped, left) in outcomes.iter() {
+            let mut all: Vec<u32> = popped.iter().map(|v| v.unwrap()).collect();
+            all.extend(left);
+            all.sort_unstable();
+            assert_eq!(all, vec![1, 2, 3]);
+            assert_eq!(left.len(), 1);
+        }
+        // 3 is left if both pops come before the push, 1 otherwise; never 2, which is above 1;
+        let left: BTreeSet<u32> = outcomes.iter().map(|(_, left)| left[0]).collect();
+        assert_eq!(left, vec![1, 3].into_iter().collect());
+        assert!(outcomes.contains(&(vec![Some(2), Some(3)], vec![1])));
+        assert!(outcomes.contains(&(vec![Some(3), Some(2)], vec![1])));
+        assert!(outcomes.contains(&(vec![Some(1), Some(2)], vec![3])));
+    }
+}
@@ -0,0 +1,511 @@
+use std::cell::Cell;
+use std::fmt;
+use std::marker::{PhantomData, PhantomPinned};
+use std::pin::Pin;
+use std::ptr::NonNull;
+
+// An intrusive list: the links live in the elements, as a ListLink field of the user's struct,
+// instead of in a Node that wraps each value (what linkedlist1::List does). So linking costs no
+// allocation, and a struct with several ListLink fields can be in as many lists at once, e.g. a
+// connection in a table and in a timer wheel slot:
+//
+//     struct Conn {
+//         id: u32,
+//         table_link: ListLink,
+//         timer_link: ListLink,
+//     }
+//
+//     struct TimerAdapter;
+//     unsafe impl Adapter for TimerAdapter {
+//         type Elem = Conn;
+//         const LINK_OFFSET: usize = std::mem::offset_of!(Conn, timer_link);
+//     }
+//
+//     let conn = Box::pin(Conn { id: 1, table_link: ListLink::new(), timer_link: ListLink::new() });
+//     let mut timers: List<TimerAdapter> = List::new();
+//     timers.push_back(conn.as_ref());
+//     conn.timer_link.unlink(); // O(1), without the list at hand
+//
+// How it stays sound:
+//     1. the list is circular, through a "sentinel" link of its own, which is what lets an element
+//        unlink itself (its neighbours are all it touches). The sentinel is heap allocated once
+//        per list, so moving the List doesn't move it;
+//     2. the elements are borrowed, pinned, for the lifetime 'a of the list, so they can neither
+//        move nor be dropped while the list may reach them; a dropped List unlinks them all;
+//     3. the links are Cells, which is what lets them be relinked through shared refs;
+//     4. a ListLink that is dropped unlinks itself, so a chain never points to a dead link even if
+//        a List is leaked (mem::forget) and its elements then go away; and Pin promises the drop
+//        runs before the memory is reused.
+pub struct List<'a, A: Adapter> {
+    sentinel: NonNull<ListLink>,
+    _elems: PhantomData<Pin<&'a A::Elem>>,
+}
+
+// The link field to embed, one per list the struct may be in at a time. It's !Unpin, and so is a
+// struct holding it: once linked, the element must stay put.
+pub struct ListLink {
+    // both None when unlinked;
+    prev: Cell<Option<NonNull<ListLink>>>,
+    next: Cell<Option<NonNull<ListLink>>>,
+    // the sentinel of the List the element is in; None when unlinked, and for a sentinel;
+    owner: Cell<Option<NonNull<ListLink>>>,
+    _pin: PhantomPinned,
+}
+
+/// Tells a List where the link field of its elements is, so it can go from an element to its
+/// link and back.
+///
+/// # Safety
+///
+/// LINK_OFFSET must be the offset of a ListLink field of Elem (which is what
+/// std::mem::offset_of! gives), and Elem must not implement Unpin.
+pub unsafe trait Adapter {
+    type Elem;
+    const LINK_OFFSET: usize;
+}
+
+impl ListLink {
+    pub const fn new() -> Self {
+        ListLink {
+            prev: Cell::new(None),
+            next: Cell::new(None),
+            owner: Cell::new(None),
+            _pin: PhantomPinned,
+        }
+    }
+
+    pub fn is_linked(&self) -> bool {
+        self.next.get().is_some()
+    }
+
+    // Take the element out of whatever list it's in, in O(1); nothing if it's in none;
+    pub fn unlink(&self) {
+        if let (Some(prev), Some(next)) = (self.prev.get(), self.next.get()) {
+            // SAFETY: a linked link's neighbours are alive, see the top of the file;
+            unsafe {
+                prev.as_ref().next.set(Some(next));
+                next.as_ref().prev.set(Some(prev));
+            }
+            self.prev.set(None);
+            self.next.set(None);
+            self.owner.set(None);
+        }
+    }
+
+    // Put `link` between `prev` and `next`, which must be adjacent in the list of `sentinel`;
+    // SAFETY: all 4 must be alive, and `link` unlinked;
+    unsafe fn link_between(
+        link: NonNull<ListLink>,
+        prev: NonNull<ListLink>,
+        next: NonNull<ListLink>,
+        sentinel: NonNull<ListLink>,
+    ) {
+        link.as_ref().prev.set(Some(prev));
+        link.as_ref().next.set(Some(next));
+        link.as_ref().owner.set(Some(sentinel));
+        prev.as_ref().next.set(Some(link));
+        next.as_ref().prev.set(Some(link));
+    }
+}
+
+impl Default for ListLink {
+    fn default() -> Self {
+        ListLink::new()
+    }
+}
+
+impl Drop for ListLink {
+    fn drop(&mut self) {
+        self.unlink();
+    }
+}
+
+impl fmt::Debug for ListLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ListLink")
+            .field("linked", &self.is_linked())
+            .finish()
+    }
+}
+
+// The link of an element. The pointer is computed from the element's, not taken from a ref to
+// the field, so that it keeps the right to the whole element, which elem_of() needs to get back
+// to it.
+fn link_of<A: Adapter>(elem: Pin<&A::Elem>) -> NonNull<ListLink> {
+    let elem = &*elem as *const A::Elem as *mut u8;
+    // SAFETY: the adapter promises there's a ListLink at that offset;
+    unsafe { NonNull::new_unchecked(elem.add(A::LINK_OFFSET) as *mut ListLink) }
+}
+
+// SAFETY: `link` must come from link_of::<A>(), and its element must be alive for 'a;
+unsafe fn elem_of<'a, A: Adapter>(link: NonNull<ListLink>) -> Pin<&'a A::Elem> {
+    let elem = (link.as_ptr() as *mut u8).sub(A::LINK_OFFSET) as *const A::Elem;
+    Pin::new_unchecked(&*elem)
+}
+
+impl<'a, A: Adapter> List<'a, A> {
+    pub fn new() -> Self {
+        let sentinel = NonNull::from(Box::leak(Box::new(ListLink {
+            prev: Cell::new(None),
+            next: Cell::new(None),
+            owner: Cell::new(None),
+            _pin: PhantomPinned,
+        })));
+        // SAFETY: just allocated; an empty list is the sentinel linked to itself;
+        unsafe {
+            sentinel.as_ref().prev.set(Some(sentinel));
+            sentinel.as_ref().next.set(Some(sentinel));
+        }
+        List {
+            sentinel,
+            _elems: PhantomData,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.first().is_none()
+    }
+
+    // Panics if the element is linked already (in this list or any other on the same field);
+    pub fn push_front(&mut self, elem: Pin<&'a A::Elem>) {
+        let link = Self::check_unlinked(elem);
+        // SAFETY: the sentinel and its next are alive, and `link` is alive for 'a;
+        unsafe {
+            let next = self.sentinel.as_ref().next.get().unwrap();
+            ListLink::link_between(link, self.sentinel, next, self.sentinel);
+        }
+    }
+
+    pub fn push_back(&mut self, elem: Pin<&'a A::Elem>) {
+        let link = Self::check_unlinked(elem);
+        // SAFETY: as in push_front();
+        unsafe {
+            let prev = self.sentinel.as_ref().prev.get().unwrap();
+            ListLink::link_between(link, prev, self.sentinel, self.sentinel);
+        }
+    }
+
+    pub fn pop_front(&mut self) -> Option<Pin<&'a A::Elem>> {
+        let link = self.first()?;
+        // SAFETY: `link` is in the list, so it's alive, and its element is alive for 'a;
+        unsafe {
+            link.as_ref().unlink();
+            Some(elem_of::<A>(link))
+        }
+    }
+
+    pub fn pop_back(&mut self) -> Option<Pin<&'a A::Elem>> {
+        let link = self.last()?;
+        // SAFETY: as in pop_front();
+        unsafe {
+            link.as_ref().unlink();
+            Some(elem_of::<A>(link))
+        }
+    }
+
+    pub fn front(&self) -> Option<Pin<&'a A::Elem>> {
+        // SAFETY: as in pop_front();
+        self.first().map(|link| unsafe { elem_of::<A>(link) })
+    }
+
+    pub fn back(&self) -> Option<Pin<&'a A::Elem>> {
+        self.last().map(|link| unsafe { elem_of::<A>(link) })
+    }
+
+    // Unlink all the elements;
+    pub fn clear(&mut self) {
+        while self.pop_front().is_some() {}
+    }
+
+    // Front to back. The iterator follows the links as they are when it gets there, so an element
+    // unlinked (or linked) behind the iterator's back is simply skipped (or visited); but once the
+    // element it's on is unlinked, or moved to another list, there's no way on, and the iteration
+    // ends.
+    pub fn iter(&self) -> Iter<'_, 'a, A> {
+        Iter {
+            cur: self.sentinel,
+            sentinel: self.sentinel,
+            _list: PhantomData,
+        }
+    }
+
+    fn first(&self) -> Option<NonNull<ListLink>> {
+        // SAFETY: the sentinel is alive while the list is;
+        let link = unsafe { self.sentinel.as_ref() }.next.get().unwrap();
+        Some(link).filter(|link| *link != self.sentinel)
+    }
+
+    fn last(&self) -> Option<NonNull<ListLink>> {
+        let link = unsafe { self.sentinel.as_ref() }.prev.get().unwrap();
+        Some(link).filter(|link| *link != self.sentinel)
+    }
+
+    fn check_unlinked(elem: Pin<&'a A::Elem>) -> NonNull<ListLink> {
+        let link = link_of::<A>(elem);
+        // SAFETY: the element is alive for 'a, so its link is;
+        assert!(
+            !unsafe { link.as_ref() }.is_linked(),
+            "element is already linked"
+        );
+        link
+    }
+}
+
+impl<'a, A: Adapter> Default for List<'a, A> {
+    fn default() -> Self {
+        List::new()
+    }
+}
+
+// Unlink the elements, which outlive the list, so none of them is left pointing at the freed
+// sentinel; then free it;
+impl<'a, A: Adapter> Drop for List<'a, A> {
+    fn drop(&mut self) {
+        self.clear();
+        // SAFETY: the sentinel came from a Box, and is linked to itself only;
+        drop(unsafe { Box::from_raw(self.sentinel.as_ptr()) });
+    }
+}
+
+impl<'a, A: Adapter> fmt::Debug for List<'a, A>
+where
+    A::Elem: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+// Keeps the link it yielded last (the sentinel at first), not the next one: that one might be
+// unlinked, and its list dropped, before the iterator gets to it, while the current one is
+// alive for 'a. A linked link's next is always alive, but only for as long as the list it's in:
+// if the current element has moved to another list, which may have elements that live less
+// than 'a, the next one is not ours to hand out, and its owner says so.
+pub struct Iter<'l, 'a, A: Adapter> {
+    cur: NonNull<ListLink>,
+    sentinel: NonNull<ListLink>,
+    _list: PhantomData<&'l List<'a, A>>,
+}
+
+impl<'l, 'a, A: Adapter> Iterator for Iter<'l, 'a, A> {
+    type Item = Pin<&'a A::Elem>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // SAFETY: `cur` is the sentinel (alive for 'l) or an element's link (alive for 'a);
+        let next = unsafe { self.cur.as_ref() }.next.get()?;
+        // the end of our list (a sentinel has no owner), or a link of another list;
+        if unsafe { next.as_ref() }.owner.get() != Some(self.sentinel) {
+            return None;
+        }
+        self.cur = next;
+        // SAFETY: a link in our list comes from link_of::<A>(), and is alive for 'a;
+        Some(unsafe { elem_of::<A>(next) })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Adapter, List, ListLink};
+    use std::mem;
+    use std::pin::Pin;
+
+    #[derive(Debug, Default)]
+    struct Conn {
+        id: u32,
+        table_link: ListLink,
+        timer_link: ListLink,
+    }
+
+    fn conn(id: u32) -> Pin<Box<Conn>> {
+        Box::pin(Conn {
+            id,
+            ..Conn::default()
+        })
+    }
+
+    struct TableAdapter;
+    unsafe impl Adapter for TableAdapter {
+        type Elem = Conn;
+        const LINK_OFFSET: usize = mem::offset_of!(Conn, table_link);
+    }
+
+    struct TimerAdapter;
+    unsafe impl Adapter for TimerAdapter {
+        type Elem = Conn;
+        const LINK_OFFSET: usize = mem::offset_of!(Conn, timer_link);
+    }
+
+    fn ids<A: Adapter<Elem = Conn>>(list: &List<'_, A>) -> Vec<u32> {
+        list.iter().map(|c| c.id).collect()
+    }
+
+    #[test]
+    fn basics() {
+        let conns: Vec<Pin<Box<Conn>>> = (0..5).map(conn).collect();
+        let mut list: List<TableAdapter> = List::new();
+        assert!(list.is_empty());
+        assert!(list.pop_front().is_none());
+        assert!(list.back().is_none());
+
+        list.push_back(conns[1].as_ref());
+        list.push_back(conns[2].as_ref());
+        list.push_front(conns[0].as_ref());
+        list.push_back(conns[3].as_ref());
+        assert_eq!(ids(&list), vec![0, 1, 2, 3]);
+        assert_eq!(list.front().map(|c| c.id), Some(0));
+        assert_eq!(list.back().map(|c| c.id), Some(3));
+        assert!(conns[2].table_link.is_linked());
+        assert!(!conns[4].table_link.is_linked());
+
+        assert_eq!(list.pop_front().map(|c| c.id), Some(0));
+        assert_eq!(list.pop_back().map(|c| c.id), Some(3));
+        assert!(!conns[0].table_link.is_linked());
+        assert_eq!(ids(&list), vec![1, 2]);
+
+        // popped elements can go back in;
+        list.push_front(conns[3].as_ref());
+        assert_eq!(format!("{:?}", list).matches("id").count(), 3);
+        list.clear();
+        assert!(list.is_empty());
+        assert!(conns.iter().all(|c| !c.table_link.is_linked()));
+    }
+
+    #[test]
+    fn test_self_unlink() {
+        let conns: Vec<Pin<Box<Conn>>> = (0..4).map(conn).collect();
+        let mut list: List<TableAdapter> = List::new();
+        for c in &conns {
+            list.push_back(c.as_ref());
+        }
+        conns[1].table_link.unlink();
+        conns[1].table_link.unlink();
+        assert_eq!(ids(&list), vec![0, 2, 3]);
+        conns[0].table_link.unlink();
+        conns[3].table_link.unlink();
+        assert_eq!(ids(&list), vec![2]);
+        assert_eq!(list.front().map(|c| c.id), Some(2));
+        assert_eq!(list.back().map(|c| c.id), Some(2));
+        conns[2].table_link.unlink();
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "already linked")]
+    fn test_link_twice() {
+        let c = conn(0);
+        let mut a: List<TableAdapter> = List::new();
+        let mut b: List<TableAdapter> = List::new();
+        a.push_back(c.as_ref());
+        b.push_back(c.as_ref());
+    }
+
+    // Each connection is in the table, and in the timer wheel slot of its deadline; a connection
+    // that closes unlinks itself from both, and rescheduling one moves it between slots.
+    #[test]
+    fn test_timer_wheel() {
+        let conns: Vec<Pin<Box<Conn>>> = (0..10).map(conn).collect();
+        let mut table: List<TableAdapter> = List::new();
+        let mut wheel: Vec<List<TimerAdapter>> = (0..4).map(|_| List::new()).collect();
+        for c in &conns {
+            table.push_back(c.as_ref());
+            wheel[c.id as usize % 4].push_back(c.as_ref());
+        }
+
+        // 5 closes;
+        conns[5].table_link.unlink();
+        conns[5].timer_link.unlink();
+        // 2 is rescheduled to slot 3;
+        conns[2].timer_link.unlink();
+        wheel[3].push_back(conns[2].as_ref());
+
+        assert_eq!(ids(&table), vec![0, 1, 2, 3, 4, 6, 7, 8, 9]);
+        let slots: Vec<Vec<u32>> = wheel.iter().map(ids).collect();
+        assert_eq!(
+            slots,
+            vec![vec![0, 4, 8], vec![1, 9], vec![6], vec![3, 7, 2]]
+        );
+
+        // slot 1 expires;
+        while let Some(c) = wheel[1].pop_front() {
+            c.table_link.unlink();
+        }
+        assert_eq!(ids(&table), vec![0, 2, 3, 4, 6, 7, 8]);
+
+        // dropping a list unlinks what's left in it, and only from it;
+        drop(wheel);
+        assert!(conns.iter().all(|c| !c.timer_link.is_linked()));
+        assert_eq!(ids(&table), vec![0, 2, 3, 4, 6, 7, 8]);
+    }
+
+    #[test]
+    fn test_iter_unlink() {
+        let conns: Vec<Pin<Box<Conn>>> = (0..4).map(conn).collect();
+        let mut list: List<TableAdapter> = List::new();
+        for c in &conns {
+            list.push_back(c.as_ref());
+        }
+        // unlinking elements ahead skips them;
+        let mut seen = vec![];
+        for c in list.iter() {
+            seen.push(c.id);
+            if c.id == 0 {
+                conns[2].table_link.unlink();
+            }
+        }
+        assert_eq!(seen, vec![0, 1, 3]);
+
+        // unlinking the current one ends the iteration;
+        let mut itr = list.iter();
+        let c = itr.next().unwrap();
+        c.table_link.unlink();
+        assert!(itr.next().is_none());
+    }
+
+    // Moving the current element to another list ends the iteration too, rather than leading
+    // into that list, whose elements may not live as long as the iterator's;
+    #[test]
+    fn test_iter_move() {
+        let conns: Vec<Pin<Box<Conn>>> = (0..2).map(conn).collect();
+        let mut list: List<TableAdapter> = List::new();
+        for c in &conns {
+            list.push_back(c.as_ref());
+        }
+        let mut itr = list.iter();
+        let c = itr.next().unwrap();
+        assert_eq!(c.id, 0);
+        {
+            let short = conn(9);
+            let mut other: List<TableAdapter> = List::new();
+            other.push_back(short.as_ref());
+            c.table_link.unlink();
+            other.push_front(c);
+            assert!(itr.next().is_none());
+            // `other` and `short` go away here;
+        }
+        assert!(itr.next().is_none());
+        assert_eq!(ids(&list), vec![1]);
+    }
+
+    // A link dropped while linked (possible only once its list is leaked) unlinks itself, so
+    // its neighbours don't point at freed memory;
+    #[test]
+    fn test_drop_linked() {
+        let a = conn(0);
+        let b = conn(1);
+        let mut list: List<TableAdapter> = List::new();
+        list.push_back(a.as_ref());
+        list.push_back(b.as_ref());
+        // what mem::forget(list) would leave behind, minus the leak: the elements still linked
+        // to the sentinel, which is left in place;
+        let sentinel = list.sentinel;
+        mem::forget(list);
+        drop(b);
+        assert_eq!(
+            unsafe { sentinel.as_ref() }.prev.get(),
+            Some(super::link_of::<TableAdapter>(a.as_ref()))
+        );
+        a.table_link.unlink();
+        assert!(!a.table_link.is_linked());
+        drop(unsafe { Box::from_raw(sentinel.as_ptr()) });
+    }
+}
@@ -1,11 +1,28 @@
-use std::iter::IntoIterator;
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::iter::Iterator;
+use std::iter::{FromIterator, IntoIterator};
+use std::marker::PhantomData;
+use std::ptr::NonNull;
 
+// The nodes are allocated by Box, but the list keeps them as raw pointers (Box::into_raw) and
+// turns them back into Box only to free them (Box::from_raw). Why not keep `Option<Box<Node<T>>>`
+// links, and only the tail as a raw pointer? Because a Box claims unique access to its node: each
+// time the Box is moved or used through `&mut`, Miri (Stacked Borrows) invalidates every other
+// pointer to the node, so the tail pointer would become dangling as far as the language is
+// concerned, even though the address is still right. With all links raw, nobody claims
+// uniqueness except the short-lived references handed out below.
 pub struct List<T> {
     head: Link<T>,
+    // the last node, for O(1) push_back(); None iff head is None;
+    tail: Link<T>,
+    len: usize,
+    // tell the compiler (drop check, variance) that List<T> owns nodes of T, like the Box did;
+    _owns: PhantomData<Box<Node<T>>>,
 }
 
-type Link<T> = Option<Box<Node<T>>>;
+type Link<T> = Option<NonNull<Node<T>>>;
 
 struct Node<T> {
     val: T,
@@ -30,7 +47,8 @@ impl<'a, T> Iterator for Itr<'a, T> {
         // 4. the closure returns a &Node<T>, say `tmp_node_ref`;
         // 5. `map()` wraps `tmp_node_ref` in Some, and move the Some to self.curr;
         self.curr.take().map(|node| {
-            self.curr = node.next.as_ref().map(|next| next.as_ref());
+            // SAFETY: `next` is a live node of the list, which is borrowed shared for 'a;
+            self.curr = node.next.map(|next| unsafe { &*next.as_ptr() });
             &node.val
         })
     }
@@ -45,38 +63,109 @@ impl<'a, T> Iterator for MutItr<'a, T> {
 
     fn next(&mut self) -> Option<Self::Item> {
         self.curr.take().map(|node| {
-            self.curr = node.next.as_mut().map(|next| next.as_mut());
+            // SAFETY: `next` is a live node of the list, which is borrowed mutably for 'a, and
+            // each node is handed out once;
+            self.curr = node.next.map(|next| unsafe { &mut *next.as_ptr() });
             &mut node.val
         })
     }
 }
 
+// A node leaves its Box here, and gets back into one in into_box();
+fn new_node<T>(val: T, next: Link<T>) -> NonNull<Node<T>> {
+    NonNull::from(Box::leak(Box::new(Node { val, next })))
+}
+
+// SAFETY: `node` came from new_node() and is no longer reachable from any list;
+unsafe fn into_box<T>(node: NonNull<Node<T>>) -> Box<Node<T>> {
+    Box::from_raw(node.as_ptr())
+}
+
+// The raw pointers make List neither Send nor Sync automatically; but it owns its nodes just like
+// the Box version did, so it's as thread safe as T is.
+unsafe impl<T: Send> Send for List<T> {}
+unsafe impl<T: Sync> Sync for List<T> {}
+unsafe impl<T: Send> Send for IntoItr<T> {}
+unsafe impl<T: Sync> Sync for IntoItr<T> {}
+// and the iterators are as thread safe as the refs they stand for: &T for Itr, &mut T for MutItr;
+unsafe impl<'a, T: Sync> Send for Itr<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for Itr<'a, T> {}
+unsafe impl<'a, T: Send> Send for MutItr<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for MutItr<'a, T> {}
+// and so is CursorMut, like &mut T;
+unsafe impl<'a, T: Send> Send for CursorMut<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for CursorMut<'a, T> {}
+
 impl<T> List<T> {
     pub fn new() -> Self {
-        List { head: None }
+        List {
+            head: None,
+            tail: None,
+            len: 0,
+            _owns: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
     }
 
     pub fn push_front(&mut self, val: T) {
-        let new_head = Box::new(Node {
-            val,
-            next: self.head.take(),
-        });
+        let new_head = new_node(val, self.head);
+        if self.tail.is_none() {
+            self.tail = Some(new_head);
+        }
         self.head = Some(new_head);
+        self.len += 1;
+    }
+
+    // O(1) by the tail pointer; with pop_front() it makes a FIFO queue;
+    pub fn push_back(&mut self, val: T) {
+        let new_tail = new_node(val, None);
+        match self.tail {
+            // SAFETY: the tail is a live node of this list, and `&mut self` means nobody else
+            // is looking at it;
+            Some(tail) => unsafe { (*tail.as_ptr()).next = Some(new_tail) },
+            None => self.head = Some(new_tail),
+        }
+        self.tail = Some(new_tail);
+        self.len += 1;
     }
 
     pub fn pop_front(&mut self) -> Option<T> {
-        self.head.take().map(|node| {
+        self.head.map(|head| {
+            // SAFETY: head is unlinked right below;
+            let node = unsafe { into_box(head) };
             self.head = node.next;
+            if self.head.is_none() {
+                self.tail = None;
+            }
+            self.len -= 1;
             node.val
         })
     }
 
+    // SAFETY of the 4 accessors below: the node is live as long as the list is, and the returned
+    // ref borrows the list (shared or mutably), so the node can neither be freed nor aliased
+    // mutably while the ref lives;
     pub fn front(&self) -> Option<&T> {
-        self.head.as_ref().map(|node| &node.val)
+        self.head.map(|node| unsafe { &(*node.as_ptr()).val })
     }
 
     pub fn front_mut(&mut self) -> Option<&mut T> {
-        self.head.as_mut().map(|node| &mut node.val)
+        self.head.map(|node| unsafe { &mut (*node.as_ptr()).val })
+    }
+
+    pub fn back(&self) -> Option<&T> {
+        self.tail.map(|node| unsafe { &(*node.as_ptr()).val })
+    }
+
+    pub fn back_mut(&mut self) -> Option<&mut T> {
+        self.tail.map(|node| unsafe { &mut (*node.as_ptr()).val })
     }
 
     //Yuanguo: the accurate version of 'fn itr()' should be this:
@@ -85,47 +174,391 @@ impl<T> List<T> {
     //but that can be elided into:
     //       pub fn itr(&self) -> Itr<T>
     pub fn itr(&self) -> Itr<T> {
-        let curr = self.head.as_ref().map(|node| node.as_ref());
+        let curr = self.head.map(|node| unsafe { &*node.as_ptr() });
         Itr { curr }
     }
 
     pub fn mut_itr(&mut self) -> MutItr<T> {
-        let curr = self.head.as_mut().map(|node| node.as_mut());
+        let curr = self.head.map(|node| unsafe { &mut *node.as_ptr() });
         MutItr { curr }
     }
+
+    // a cursor on the "ghost" before the head, see CursorMut;
+    pub fn cursor_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {
+            cur: None,
+            index: 0,
+            list: self,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        while self.pop_front().is_some() {}
+    }
+
+    // Move all the elements of `other` to the back of this list, in O(1); `other` is left empty;
+    pub fn append(&mut self, other: &mut List<T>) {
+        let other_head = match other.head.take() {
+            Some(head) => head,
+            None => return,
+        };
+        match self.tail {
+            // SAFETY: see push_back(); the nodes of `other` belong to this list from now on;
+            Some(tail) => unsafe { (*tail.as_ptr()).next = Some(other_head) },
+            None => self.head = Some(other_head),
+        }
+        self.tail = other.tail.take();
+        self.len += other.len;
+        other.len = 0;
+    }
+
+    // Split the list in two at `at`: this list keeps [0, at), and the rest is returned; O(at).
+    // Panics if at > len.
+    pub fn split_off(&mut self, at: usize) -> List<T> {
+        assert!(
+            at <= self.len,
+            "List::split_off: index {} out of {}",
+            at,
+            self.len
+        );
+        let mut cursor = self.cursor_mut();
+        for _ in 0..at {
+            cursor.move_next();
+        }
+        cursor.split_after()
+    }
+
+    // Turn every `next` around, in place; the head becomes the tail;
+    pub fn reverse(&mut self) {
+        let mut prev: Link<T> = None;
+        let mut curr = self.head;
+        self.tail = self.head;
+        while let Some(node) = curr {
+            // SAFETY: `node` is a node of this list, and `&mut self` means nobody else is
+            // looking at it;
+            unsafe {
+                curr = (*node.as_ptr()).next;
+                (*node.as_ptr()).next = prev;
+            }
+            prev = Some(node);
+        }
+        self.head = prev;
+    }
+
+    // Keep only the elements for which `f` returns true, in order;
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&T) -> bool,
+    {
+        let mut cursor = self.cursor_mut();
+        while let Some(val) = cursor.peek_next() {
+            if f(val) {
+                cursor.move_next();
+            } else {
+                cursor.remove_after();
+            }
+        }
+    }
+
+    // Remove consecutive repeated elements, keeping the first of each run;
+    pub fn dedup(&mut self)
+    where
+        T: PartialEq,
+    {
+        self.dedup_by(|a, b| a == b);
+    }
+
+    pub fn dedup_by_key<K, F>(&mut self, mut key: F)
+    where
+        K: PartialEq,
+        F: FnMut(&mut T) -> K,
+    {
+        self.dedup_by(|a, b| key(a) == key(b));
+    }
+
+    // Like Vec::dedup_by(): `same_bucket(a, b)` is called with `a` the element after `b`, and `a`
+    // is removed if it returns true;
+    pub fn dedup_by<F>(&mut self, mut same_bucket: F)
+    where
+        F: FnMut(&mut T, &mut T) -> bool,
+    {
+        let mut kept = match self.head {
+            Some(node) => node,
+            None => return,
+        };
+        // SAFETY: `kept` and `next` are 2 different nodes of this list, so the 2 refs don't
+        // alias; `next` is unlinked before it's freed;
+        unsafe {
+            while let Some(next) = (*kept.as_ptr()).next {
+                if same_bucket(&mut (*next.as_ptr()).val, &mut (*kept.as_ptr()).val) {
+                    let node = into_box(next);
+                    (*kept.as_ptr()).next = node.next;
+                    if self.tail == Some(next) {
+                        self.tail = Some(kept);
+                    }
+                    self.len -= 1;
+                } else {
+                    kept = next;
+                }
+            }
+        }
+    }
+
+    pub fn sort(&mut self)
+    where
+        T: Ord,
+    {
+        self.sort_by(|a, b| a.cmp(b));
+    }
+
+    // Bottom-up merge sort, relinking the nodes in place, O(n*log(n)) and stable. Each pass
+    // merges the runs of `width` nodes pairwise into runs of 2*width (the first run of a pair
+    // wins the ties, which keeps it stable), and the last pass is the one that did a single merge.
+    // No recursion and no allocation: besides the list itself, only a few pointers.
+    //
+    // The nodes are taken out of the list for a pass, by a SortPass, which puts them all back
+    // when it's dropped: at the end of the pass, or if `compare` panics. So a panic leaves the
+    // list valid, with all its values (in some unspecified order), the same as slice::sort_by().
+    pub fn sort_by<F>(&mut self, mut compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        let mut width = 1;
+        loop {
+            let merges = SortPass::new(self).run(width, &mut compare);
+            if merges <= 1 {
+                break;
+            }
+            width *= 2;
+        }
+    }
+}
+
+// A pass of sort_by(). Every node of the list is in exactly one of the chains below at any time,
+// so that drop() can put them all back into the list, whether the pass is done or not.
+struct SortPass<'a, T> {
+    list: &'a mut List<T>,
+    // (head, tail) of the runs merged so far, and of the merge in progress;
+    out: (Link<T>, Link<T>),
+    merged: (Link<T>, Link<T>),
+    // the 2 runs being merged, and the nodes after them;
+    a: Link<T>,
+    b: Link<T>,
+    rest: Link<T>,
+}
+
+impl<'a, T> SortPass<'a, T> {
+    fn new(list: &'a mut List<T>) -> Self {
+        let rest = list.head.take();
+        list.tail = None;
+        SortPass {
+            list,
+            out: (None, None),
+            merged: (None, None),
+            a: None,
+            b: None,
+            rest,
+        }
+    }
+
+    // Merge the runs of `width` nodes pairwise, and return the number of merges;
+    fn run<F>(mut self, width: usize, compare: &mut F) -> usize
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        let mut merges = 0;
+        // SAFETY: the chains hold the nodes of the list, which is borrowed mutably, so nobody
+        // else is looking at them;
+        unsafe {
+            while self.rest.is_some() {
+                self.a = self.rest.take();
+                self.b = cut_after(self.a, width);
+                self.rest = cut_after(self.b, width);
+                self.merge(compare);
+                merges += 1;
+            }
+        }
+        merges
+    }
+
+    // Merge `a` and `b` into `merged`, taking from `a` unless the node of `b` is strictly less,
+    // then move `merged` to the end of `out`. A node leaves `a` or `b` only once `compare` is
+    // done with it, so a panic finds it in one of them.
+    // SAFETY: see run();
+    unsafe fn merge<F>(&mut self, compare: &mut F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        loop {
+            let node = match (self.a, self.b) {
+                (Some(x), Some(y)) => {
+                    if compare(&(*y.as_ptr()).val, &(*x.as_ptr()).val) == Ordering::Less {
+                        self.b = (*y.as_ptr()).next;
+                        y
+                    } else {
+                        self.a = (*x.as_ptr()).next;
+                        x
+                    }
+                }
+                // one of them is used up: the other one goes as it is;
+                (Some(_), None) | (None, Some(_)) => {
+                    let chain = chain(self.a.take().or_else(|| self.b.take()));
+                    self.merged = append(self.merged, chain);
+                    break;
+                }
+                (None, None) => break,
+            };
+            self.merged = append(self.merged, (Some(node), Some(node)));
+        }
+        let merged = std::mem::take(&mut self.merged);
+        self.out = append(self.out, merged);
+    }
+}
+
+impl<'a, T> Drop for SortPass<'a, T> {
+    fn drop(&mut self) {
+        // SAFETY: see run(); the tails of the chains are relinked, so whatever they still point
+        // to doesn't matter;
+        unsafe {
+            let mut all = append(self.out, self.merged);
+            for link in [self.a, self.b, self.rest].iter() {
+                all = append(all, chain(*link));
+            }
+            if let Some(tail) = all.1 {
+                (*tail.as_ptr()).next = None;
+            }
+            self.list.head = all.0;
+            self.list.tail = all.1;
+        }
+    }
+}
+
+// (head, tail) of the chain starting at `link`, which ends with a None next;
+// SAFETY: see cut_after();
+unsafe fn chain<T>(link: Link<T>) -> (Link<T>, Link<T>) {
+    let mut last = match link {
+        Some(node) => node,
+        None => return (None, None),
+    };
+    while let Some(next) = (*last.as_ptr()).next {
+        last = next;
+    }
+    (link, Some(last))
+}
+
+// Link chain `b` after chain `a`, both given as (head, tail), and return the whole;
+// SAFETY: see cut_after();
+unsafe fn append<T>(a: (Link<T>, Link<T>), b: (Link<T>, Link<T>)) -> (Link<T>, Link<T>) {
+    match (a.1, b.0) {
+        (_, None) => a,
+        (None, _) => b,
+        (Some(tail), head) => {
+            (*tail.as_ptr()).next = head;
+            (a.0, b.1)
+        }
+    }
+}
+
+// Cut the chain starting at `link` after its first `n` nodes, and return the rest (None if the
+// chain has no more than `n` nodes).
+// SAFETY: `link` must start a chain of live nodes that nobody else is looking at;
+unsafe fn cut_after<T>(link: Link<T>, n: usize) -> Link<T> {
+    let mut node = link?;
+    for _ in 1..n {
+        node = (*node.as_ptr()).next?;
+    }
+    (*node.as_ptr()).next.take()
+}
+
+impl<T> Default for List<T> {
+    fn default() -> Self {
+        List::new()
+    }
+}
+
+// Printed like a slice, e.g. [1, 2, 3];
+impl<T: fmt::Debug> fmt::Debug for List<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.itr()).finish()
+    }
+}
+
+// push_back() one by one, so it's iterative (a derived Clone of a Box chain would recurse) and
+// keeps the order;
+impl<T: Clone> Clone for List<T> {
+    fn clone(&self) -> Self {
+        self.itr().cloned().collect()
+    }
+}
+
+// Comparisons and hashing are by content, like a slice;
+impl<T: PartialEq> PartialEq for List<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.itr().eq(other.itr())
+    }
+}
+
+impl<T: Eq> Eq for List<T> {}
+
+impl<T: PartialOrd> PartialOrd for List<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.itr().partial_cmp(other.itr())
+    }
+}
+
+impl<T: Ord> Ord for List<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.itr().cmp(other.itr())
+    }
+}
+
+// The length goes first, like for a slice, so that e.g. ([1], [2]) and ([1, 2], []) don't feed
+// the same sequence to the hasher;
+impl<T: Hash> Hash for List<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len);
+        for val in self.itr() {
+            val.hash(state);
+        }
+    }
 }
 
 impl<T> Drop for List<T> {
     fn drop(&mut self) {
         // self.head.take():  it's mem::replace under the hood, setting self.head to None and returning
-        // its original value to head;
+        // its original value to head; the tail pointer goes with it;
         let mut head = self.head.take();
+        self.tail = None;
+        self.len = 0;
 
-        // `while let` is a shortcut of match; here we match value (`head`) by value (`Some(mut node)`):
-        //          the Box<Node<T>> instance in `head` is moved to `node`;
-        // that's to say, `head` is partial moved; that's OK, we will NOT access it any more: the variable
-        // `head` is re-assigned with another value soon;
-        while let Some(mut node) = head {
-            // the main resource, Box<Node<T>> instance, has been moved to `node` now, let us see how it
-            // gets dropped;
+        // `while let` is a shortcut of match; here we match value (`head`) by value (`Some(node)`):
+        //          with Box links the Box<Node<T>> instance in `head` was moved to `node`, and `head`
+        //          partial moved; a NonNull is Copy, so now the pointer is simply copied, and it's
+        //          into_box() that takes the ownership of the node back, as a Box<Node<T>>;
+        // `head` is re-assigned with another value soon anyway;
+        while let Some(node) = head {
+            // SAFETY: the node came from new_node(), and the list doesn't reach it any more: the
+            // links to it were taken (self.head above, or the previous node's next below);
+            let mut node = unsafe { into_box(node) };
 
             // 1. `next` is set to None here (remember take() is mem::replace); so `node` becomes an
             //     instance of: Box<Node{val:val, next:None}>;
             head = node.next.take();
 
-            // this should be fine too: the only difference is that node.next is moved instead of set
-            // to None by `take()`, left as uninitialized;
+            // this should be fine too: the only difference is that node.next is copied (NonNull is
+            // Copy) instead of set to None by `take()`; the dropped node doesn't follow it anyway;
             //head = node.next;
 
             // 2. `node` goes out of scope and gets dropped here;
-            //    remember the inside Node's `next` has been set to None by take() method (or initialized),
-            //    so no unbounded recursion occurs.
+            //    a raw pointer doesn't own what it points to, so dropping the Node never drops the
+            //    next one: no unbounded recursion occurs.
         }
     }
 }
 
 pub struct IntoItr<T> {
     curr: Link<T>,
+    _owns: PhantomData<Box<Node<T>>>,
 }
 
 impl<T> Iterator for IntoItr<T> {
@@ -136,16 +569,42 @@ impl<T> Iterator for IntoItr<T> {
         //    its original value, say origin;
         // 2. origin.map(): note that map() takes self (not &self) as argument, so origin is moved;
         // 3. if origin is None, do nothing (remember that curr was left None in step-1);
-        // 4. if origin is Some(T), T is moved (or copied if it's Copy) to closure, that's to say
-        //    the Box<Node<T>> object inside origin is moved to node, which will be destroyed when
-        //    the closure ends; that is where each node of the list gets destroyed;
+        // 4. if origin is Some(T), T is copied (NonNull is Copy) to closure, and into_box() turns
+        //    it back into the Box<Node<T>> object, which will be destroyed when the closure ends;
+        //    that is where each node of the list gets destroyed;
         self.curr.take().map(|node| {
+            // SAFETY: the iterator owns the nodes from `curr` on, and `curr` no longer points here;
+            let node = unsafe { into_box(node) };
             self.curr = node.next;
             node.val
         })
     }
 }
 
+// An iterator dropped halfway still owns the rest of the nodes; free them the same way;
+impl<T> Drop for IntoItr<T> {
+    fn drop(&mut self) {
+        while self.next().is_some() {}
+    }
+}
+
+// push_back() keeps the order of the input, unlike push_front();
+impl<T> Extend<T> for List<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for val in iter {
+            self.push_back(val);
+        }
+    }
+}
+
+impl<T> FromIterator<T> for List<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut list = List::new();
+        list.extend(iter);
+        list
+    }
+}
+
 impl<T> IntoIterator for List<T> {
     type Item = T;
     type IntoIter = IntoItr<T>;
@@ -154,20 +613,150 @@ impl<T> IntoIterator for List<T> {
      * this will not work, see test_drop_move() below.
      *
     fn into_iter(self) -> Self::IntoIter {
-        IntoItr { curr: self.head }
+        IntoItr { curr: self.head, _owns: PhantomData }
     }
     */
 
+    // the list gives its nodes away, and drops empty;
     fn into_iter(mut self) -> Self::IntoIter {
+        self.tail = None;
+        self.len = 0;
         IntoItr {
             curr: self.head.take(),
+            _owns: PhantomData,
+        }
+    }
+}
+
+// So that `for v in &list` and `for v in &mut list` work, through Itr and MutItr;
+impl<'a, T> IntoIterator for &'a List<T> {
+    type Item = &'a T;
+    type IntoIter = Itr<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.itr()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut List<T> {
+    type Item = &'a mut T;
+    type IntoIter = MutItr<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.mut_itr()
+    }
+}
+
+// A cursor that walks the list forward and edits it right after where it stands, all in O(1)
+// (except splitting and splicing, which are O(1) too thanks to the tail pointer). Besides the
+// nodes, it can be on a "ghost" position before the head, which is where it starts: from there
+// "after" means at the front. Moving next from the tail goes back to the ghost.
+pub struct CursorMut<'a, T> {
+    // None for the ghost;
+    cur: Link<T>,
+    // index of `cur`, meaningless on the ghost;
+    index: usize,
+    list: &'a mut List<T>,
+}
+
+impl<'a, T> CursorMut<'a, T> {
+    pub fn index(&self) -> Option<usize> {
+        self.cur.map(|_| self.index)
+    }
+
+    pub fn move_next(&mut self) {
+        self.index = match self.cur {
+            Some(_) => self.index + 1,
+            None => 0,
+        };
+        self.cur = *self.next_link();
+    }
+
+    // SAFETY of the 2 accessors below: the ref borrows the cursor mutably, so the cursor (the
+    // only way to the list) can't move, edit, or hand out another ref while it lives;
+    pub fn current(&mut self) -> Option<&mut T> {
+        self.cur.map(|node| unsafe { &mut (*node.as_ptr()).val })
+    }
+
+    pub fn peek_next(&mut self) -> Option<&mut T> {
+        self.next_link()
+            .map(|node| unsafe { &mut (*node.as_ptr()).val })
+    }
+
+    pub fn insert_after(&mut self, val: T) {
+        let next_link = self.next_link();
+        let new_node = new_node(val, *next_link);
+        *next_link = Some(new_node);
+        if self.list.tail == self.cur {
+            self.list.tail = Some(new_node);
+        }
+        self.list.len += 1;
+    }
+
+    pub fn remove_after(&mut self) -> Option<T> {
+        let next_link = self.next_link();
+        let next = (*next_link)?;
+        // SAFETY: `next` is unlinked right below;
+        let node = unsafe { into_box(next) };
+        *next_link = node.next;
+        if self.list.tail == Some(next) {
+            self.list.tail = self.cur;
+        }
+        self.list.len -= 1;
+        Some(node.val)
+    }
+
+    // Cut the list after the cursor: the rest is returned as a new list, and the current node
+    // becomes the tail (on the ghost, the whole list goes). The index tells how many stay, so
+    // it's O(1).
+    pub fn split_after(&mut self) -> List<T> {
+        let mut rest = List::new();
+        rest.head = self.next_link().take();
+        if rest.head.is_some() {
+            let kept = self.index().map_or(0, |i| i + 1);
+            rest.tail = self.list.tail;
+            rest.len = self.list.len - kept;
+            self.list.tail = self.cur;
+            self.list.len = kept;
+        }
+        rest
+    }
+
+    // Insert the whole `other` list after the cursor, without touching its nodes but the tail;
+    // the cursor stays where it is.
+    pub fn splice_after(&mut self, mut other: List<T>) {
+        let (other_head, other_tail) = match (other.head.take(), other.tail.take()) {
+            (Some(head), Some(tail)) => (head, tail),
+            _ => return,
+        };
+        let next_link = self.next_link();
+        // SAFETY: `other_tail` is the last node of `other`, whose nodes now belong to this list;
+        unsafe { (*other_tail.as_ptr()).next = *next_link };
+        *next_link = Some(other_head);
+        if self.list.tail == self.cur {
+            self.list.tail = Some(other_tail);
+        }
+        self.list.len += other.len;
+        other.len = 0;
+    }
+
+    // The link after the cursor: the head on the ghost, or the `next` of the current node;
+    // SAFETY: the current node is a node of the list, which the cursor borrows mutably;
+    fn next_link(&mut self) -> &mut Link<T> {
+        match self.cur {
+            Some(node) => unsafe { &mut (*node.as_ptr()).next },
+            None => &mut self.list.head,
         }
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::List;
+    use super::{CursorMut, IntoItr, Itr, List, MutItr};
+    use crate::prng::Prng;
+    use std::collections::HashSet;
+    use std::iter::FromIterator;
+    use std::rc::Rc;
 
     #[test]
     fn basics() {
@@ -294,6 +883,490 @@ mod test {
         assert_eq!(None, mut_itr2.next());
     }
 
+    #[test]
+    fn test_queue() {
+        let mut l: List<i32> = List::new();
+        assert_eq!(l.back(), None);
+        assert_eq!(l.back_mut(), None);
+
+        l.push_back(1);
+        assert_eq!(l.front(), Some(&1));
+        assert_eq!(l.back(), Some(&1));
+        l.push_back(2);
+        l.push_front(0);
+        l.push_back(3);
+        assert_eq!(l.back(), Some(&3));
+        if let Some(v) = l.back_mut() {
+            *v *= 10;
+        }
+
+        let c: Vec<&i32> = l.itr().collect();
+        assert_eq!(c, vec![&0, &1, &2, &30]);
+
+        // the tail must be reset when the list becomes empty, and set again by the next push;
+        assert_eq!(l.pop_front(), Some(0));
+        assert_eq!(l.pop_front(), Some(1));
+        assert_eq!(l.pop_front(), Some(2));
+        assert_eq!(l.back(), Some(&30));
+        assert_eq!(l.pop_front(), Some(30));
+        assert_eq!(l.back(), None);
+        assert_eq!(l.pop_front(), None);
+        l.push_front(5);
+        assert_eq!(l.back(), Some(&5));
+        l.push_back(6);
+        assert_eq!(l.front(), Some(&5));
+        assert_eq!(l.back(), Some(&6));
+
+        // mutate through the iterator, then through the tail again;
+        for v in l.mut_itr() {
+            *v += 1;
+        }
+        l.push_back(8);
+        *l.back_mut().unwrap() += 1;
+        let c: Vec<i32> = l.into_iter().collect();
+        assert_eq!(c, vec![6, 7, 9]);
+    }
+
+    // a FIFO queue that never drains completely, and a partially consumed IntoItr (the rest is
+    // dropped by the iterator);
+    #[test]
+    fn test_queue_long() {
+        let mut l: List<String> = List::new();
+        let mut next_out = 0;
+        let n = if cfg!(miri) { 200 } else { 1000 };
+        for i in 0..n {
+            l.push_back(i.to_string());
+            if i % 3 == 2 {
+                assert_eq!(l.pop_front(), Some(next_out.to_string()));
+                next_out += 1;
+            }
+        }
+        assert_eq!(l.back(), Some(&(n - 1).to_string()));
+        let mut itr = l.into_iter();
+        assert_eq!(itr.next(), Some(next_out.to_string()));
+    }
+
+    fn from_vec(vals: Vec<i32>) -> List<i32> {
+        let mut l = List::new();
+        for v in vals {
+            l.push_back(v);
+        }
+        l
+    }
+
+    fn to_vec(l: &List<i32>) -> Vec<i32> {
+        l.itr().cloned().collect()
+    }
+
+    #[test]
+    fn test_cursor() {
+        let mut l = from_vec(vec![1, 2, 3]);
+        let mut cursor = l.cursor_mut();
+
+        // on the ghost, "after" is the front;
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.peek_next(), Some(&mut 1));
+        cursor.insert_after(0);
+        cursor.move_next();
+        assert_eq!(cursor.current(), Some(&mut 0));
+
+        cursor.move_next();
+        cursor.insert_after(15);
+        assert_eq!(cursor.remove_after(), Some(15));
+        assert_eq!(cursor.remove_after(), Some(2));
+        *cursor.peek_next().unwrap() = 30;
+
+        // at the tail: nothing to remove, and an insert makes a new tail;
+        cursor.move_next();
+        assert_eq!(cursor.peek_next(), None);
+        assert_eq!(cursor.remove_after(), None);
+        cursor.insert_after(4);
+        assert_eq!(l.back(), Some(&4));
+        assert_eq!(to_vec(&l), vec![0, 1, 30, 4]);
+
+        // removing the tail moves the tail back to the cursor; then past the tail is the ghost;
+        let mut cursor = l.cursor_mut();
+        cursor.move_next();
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(cursor.remove_after(), Some(4));
+        cursor.move_next();
+        assert_eq!(cursor.current(), None);
+        assert_eq!(l.back(), Some(&30));
+        l.push_back(5);
+        assert_eq!(to_vec(&l), vec![0, 1, 30, 5]);
+
+        let mut empty: List<i32> = List::new();
+        let mut cursor = empty.cursor_mut();
+        cursor.move_next();
+        assert_eq!(cursor.remove_after(), None);
+        cursor.insert_after(1);
+        assert_eq!(cursor.remove_after(), Some(1));
+        assert_eq!(empty.back(), None);
+    }
+
+    #[test]
+    fn test_split_splice() {
+        let mut l = from_vec(vec![1, 2, 3, 4, 5]);
+        let mut cursor = l.cursor_mut();
+        cursor.move_next();
+        cursor.move_next();
+        let mut rest = cursor.split_after();
+        assert_eq!(to_vec(&l), vec![1, 2]);
+        assert_eq!(to_vec(&rest), vec![3, 4, 5]);
+        assert_eq!(l.back(), Some(&2));
+        assert_eq!(rest.back(), Some(&5));
+
+        // splice in the middle: the cursor stays, the tail doesn't change;
+        let mut cursor = rest.cursor_mut();
+        cursor.move_next();
+        cursor.splice_after(from_vec(vec![31, 32]));
+        assert_eq!(cursor.current(), Some(&mut 3));
+        cursor.splice_after(List::new());
+        assert_eq!(to_vec(&rest), vec![3, 31, 32, 4, 5]);
+        assert_eq!(rest.back(), Some(&5));
+
+        // splice at the tail and at the front;
+        let mut cursor = l.cursor_mut();
+        cursor.splice_after(from_vec(vec![-1, 0]));
+        cursor.move_next();
+        cursor.move_next();
+        cursor.move_next();
+        cursor.move_next();
+        cursor.splice_after(rest);
+        assert_eq!(l.back(), Some(&5));
+        l.push_back(6);
+        assert_eq!(to_vec(&l), vec![-1, 0, 1, 2, 3, 31, 32, 4, 5, 6]);
+
+        // split on the ghost takes everything, and at the tail nothing;
+        let mut all = l.cursor_mut().split_after();
+        assert_eq!(l.front(), None);
+        assert_eq!(l.back(), None);
+        assert_eq!(all.back(), Some(&6));
+        let mut cursor = all.cursor_mut();
+        for _ in 0..10 {
+            cursor.move_next();
+        }
+        let nothing = cursor.split_after();
+        assert_eq!(nothing.front(), None);
+        assert_eq!(nothing.back(), None);
+        assert_eq!(to_vec(&all).len(), 10);
+    }
+
+    #[test]
+    fn test_reverse() {
+        let mut l = from_vec(vec![1, 2, 3, 4]);
+        l.reverse();
+        assert_eq!(to_vec(&l), vec![4, 3, 2, 1]);
+        assert_eq!(l.back(), Some(&1));
+        l.push_back(0);
+        assert_eq!(to_vec(&l), vec![4, 3, 2, 1, 0]);
+
+        let mut one = from_vec(vec![1]);
+        one.reverse();
+        assert_eq!(one.back(), Some(&1));
+        let mut empty: List<i32> = List::new();
+        empty.reverse();
+        assert_eq!(empty.front(), None);
+        assert_eq!(empty.back(), None);
+    }
+
+    #[test]
+    fn test_sort() {
+        // stable: equal keys keep their order;
+        let mut l: List<(u32, char)> = List::new();
+        for pair in [(2, 'a'), (1, 'b'), (2, 'c'), (0, 'd'), (1, 'e'), (2, 'f')].iter() {
+            l.push_back(*pair);
+        }
+        l.sort_by(|a, b| a.0.cmp(&b.0));
+        let c: Vec<char> = l.itr().map(|p| p.1).collect();
+        assert_eq!(c, vec!['d', 'b', 'e', 'a', 'c', 'f']);
+        assert_eq!(l.back(), Some(&(2, 'f')));
+
+        // all the lengths around a few powers of 2, against Vec::sort();
+        let mut rng = Prng::new(43);
+        let max_n = if cfg!(miri) { 20 } else { 40 };
+        for n in 0..max_n {
+            let vals: Vec<i32> = (0..n).map(|_| rng.gen_range(10) as i32).collect();
+            let mut l = from_vec(vals.clone());
+            l.sort();
+            let mut expect = vals;
+            expect.sort();
+            assert_eq!(to_vec(&l), expect);
+            assert_eq!(l.back(), expect.last());
+            l.push_back(100);
+            assert_eq!(l.back(), Some(&100));
+        }
+
+        let mut l = from_vec((0..100).collect());
+        l.sort_by(|a, b| b.cmp(a));
+        assert_eq!(to_vec(&l), (0..100).rev().collect::<Vec<i32>>());
+        assert_eq!(l.len(), 100);
+    }
+
+    // A compare that panics halfway through any pass leaves a valid list behind, which still
+    // owns all the values: each is dropped exactly once, with the list;
+    #[test]
+    fn test_sort_panic() {
+        let n = 37;
+        for panic_at in [0, 1, 20, 60, 150].iter() {
+            let tracker = Rc::new(());
+            let mut l: List<(u32, Rc<()>)> = List::new();
+            for i in 0..n {
+                l.push_back(((i * 7) % n, Rc::clone(&tracker)));
+            }
+            let mut calls = 0;
+            let r = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                l.sort_by(|a, b| {
+                    calls += 1;
+                    if calls > *panic_at {
+                        panic!("compare");
+                    }
+                    a.0.cmp(&b.0)
+                });
+            }));
+            assert!(r.is_err());
+            assert_eq!(l.len(), n as usize);
+            let mut keys: Vec<u32> = l.itr().map(|p| p.0).collect();
+            keys.sort();
+            assert_eq!(keys, (0..n).collect::<Vec<u32>>());
+            assert_eq!(Rc::strong_count(&tracker), n as usize + 1);
+
+            // the tail is right too;
+            l.push_back((n, Rc::clone(&tracker)));
+            assert_eq!(l.back().map(|p| p.0), Some(n));
+            l.sort_by(|a, b| a.0.cmp(&b.0));
+            assert_eq!(
+                l.itr().map(|p| p.0).collect::<Vec<u32>>(),
+                (0..=n).collect::<Vec<u32>>()
+            );
+
+            drop(l);
+            assert_eq!(Rc::strong_count(&tracker), 1);
+        }
+    }
+
+    #[test]
+    fn test_dedup_retain() {
+        let mut l = from_vec(vec![1, 1, 2, 3, 3, 3, 1, 4, 4]);
+        l.dedup();
+        assert_eq!(to_vec(&l), vec![1, 2, 3, 1, 4]);
+        assert_eq!(l.back(), Some(&4));
+        l.push_back(5);
+
+        // by key: the first of each run stays;
+        l.dedup_by_key(|v| *v / 2);
+        assert_eq!(to_vec(&l), vec![1, 2, 1, 4]);
+        assert_eq!(l.back(), Some(&4));
+
+        let mut l = from_vec((0..10).collect());
+        l.retain(|v| v % 3 != 0);
+        assert_eq!(to_vec(&l), vec![1, 2, 4, 5, 7, 8]);
+        assert_eq!(l.back(), Some(&8));
+        l.retain(|v| *v < 5);
+        assert_eq!(l.back(), Some(&4));
+        l.retain(|_| false);
+        assert_eq!(l.front(), None);
+        assert_eq!(l.back(), None);
+        l.push_back(1);
+        assert_eq!(l.front(), Some(&1));
+
+        let mut strings: List<String> = List::new();
+        for s in ["a", "a", "b"].iter() {
+            strings.push_back(s.to_string());
+        }
+        strings.dedup();
+        assert_eq!(strings.itr().count(), 2);
+    }
+
+    #[test]
+    fn test_bulk() {
+        let mut l: List<i32> = (1..=3).collect();
+        assert_eq!(to_vec(&l), vec![1, 2, 3]);
+        assert_eq!(l.len(), 3);
+        l.extend(vec![4, 5]);
+        assert_eq!(to_vec(&l), vec![1, 2, 3, 4, 5]);
+
+        let mut other: List<i32> = vec![6, 7].into_iter().collect();
+        l.append(&mut other);
+        assert!(other.is_empty());
+        assert_eq!(other.back(), None);
+        assert_eq!(l.len(), 7);
+        assert_eq!(l.back(), Some(&7));
+        l.append(&mut other);
+        assert_eq!(l.len(), 7);
+        other.append(&mut l);
+        assert!(l.is_empty());
+        assert_eq!(l.front(), None);
+        assert_eq!(other.len(), 7);
+
+        let mut rest = other.split_off(5);
+        assert_eq!(to_vec(&other), vec![1, 2, 3, 4, 5]);
+        assert_eq!(to_vec(&rest), vec![6, 7]);
+        assert_eq!((other.len(), rest.len()), (5, 2));
+        assert_eq!(rest.split_off(2).len(), 0);
+        let mut all = other.split_off(0);
+        assert_eq!((other.len(), all.len()), (0, 5));
+        assert_eq!(all.back(), Some(&5));
+        all.clear();
+        assert!(all.is_empty());
+        assert_eq!(all.back(), None);
+        all.push_back(1);
+        assert_eq!(all.len(), 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_split_off_out_of_range() {
+        let mut l: List<i32> = (0..3).collect();
+        l.split_off(4);
+    }
+
+    // Random edits checked against a Vec, with the length and the tail checked after each one;
+    #[test]
+    fn test_random_len() {
+        let mut rng = Prng::new(44);
+        let mut l: List<i32> = List::new();
+        let mut model: Vec<i32> = Vec::new();
+        let rounds = if cfg!(miri) { 300 } else { 2000 };
+        for _ in 0..rounds {
+            let val = rng.gen_range(20) as i32;
+            let pos = rng.gen_range(model.len() + 1);
+            match rng.gen_range(10) {
+                0 => {
+                    l.push_front(val);
+                    model.insert(0, val);
+                }
+                1 => {
+                    l.push_back(val);
+                    model.push(val);
+                }
+                2 => {
+                    let expect = if model.is_empty() {
+                        None
+                    } else {
+                        Some(model.remove(0))
+                    };
+                    assert_eq!(l.pop_front(), expect);
+                }
+                3 => {
+                    // the cursor on the ghost, then on nodes 0, 1, ...; insert at `pos`;
+                    let mut cursor = l.cursor_mut();
+                    for _ in 0..pos {
+                        cursor.move_next();
+                    }
+                    cursor.insert_after(val);
+                    model.insert(pos, val);
+                }
+                4 => {
+                    let mut cursor = l.cursor_mut();
+                    for _ in 0..pos {
+                        cursor.move_next();
+                    }
+                    let expect = if pos < model.len() {
+                        Some(model.remove(pos))
+                    } else {
+                        None
+                    };
+                    assert_eq!(cursor.remove_after(), expect);
+                }
+                5 => {
+                    let mut rest = l.split_off(pos);
+                    assert_eq!(rest.len(), model.len() - pos);
+                    rest.push_back(val);
+                    model.push(val);
+                    l.append(&mut rest);
+                }
+                6 => {
+                    let mut cursor = l.cursor_mut();
+                    for _ in 0..pos {
+                        cursor.move_next();
+                    }
+                    cursor.splice_after(vec![val, val].into_iter().collect());
+                    model.insert(pos, val);
+                    model.insert(pos, val);
+                }
+                7 => {
+                    l.retain(|v| *v != val);
+                    model.retain(|v| *v != val);
+                }
+                8 => {
+                    l.dedup_by_key(|v| *v / 4);
+                    model.dedup_by_key(|v| *v / 4);
+                }
+                _ => {
+                    if val % 2 == 0 {
+                        l.sort();
+                        model.sort();
+                    } else {
+                        l.reverse();
+                        model.reverse();
+                    }
+                }
+            }
+            assert_eq!(l.len(), model.len());
+            assert_eq!(l.back(), model.last());
+        }
+        assert_eq!(to_vec(&l), model);
+    }
+
+    #[test]
+    fn test_traits() {
+        let mut l: List<i32> = (1..=3).collect();
+        assert_eq!(format!("{:?}", l), "[1, 2, 3]");
+        assert_eq!(format!("{:?}", List::<i32>::default()), "[]");
+
+        let c = l.clone();
+        assert_eq!(l, c);
+        l.push_back(4);
+        assert_ne!(l, c);
+        assert!(c < l);
+        assert!(List::from_iter(vec![1, 5]) > l);
+        assert!(List::<i32>::new() < c);
+
+        let mut lists = vec![l.clone(), c.clone(), List::new()];
+        lists.sort();
+        assert_eq!(lists, vec![List::new(), c.clone(), l.clone()]);
+
+        let mut set = HashSet::new();
+        set.insert(c);
+        assert!(set.contains(&(1..=3).collect::<List<i32>>()));
+        assert!(!set.contains(&l));
+
+        let mut sum = 0;
+        for v in &l {
+            sum += *v;
+        }
+        assert_eq!(sum, 10);
+        for v in &mut l {
+            *v *= 2;
+        }
+        assert_eq!(l, vec![2, 4, 6, 8].into_iter().collect());
+
+        // a long clone and compare, no recursion;
+        let n = if cfg!(miri) { 1000 } else { 100_000 };
+        let long: List<u32> = (0..n).collect();
+        let copy = long.clone();
+        assert_eq!(copy, long);
+        assert_eq!(copy.back(), Some(&(n - 1)));
+    }
+
+    // Compiles only if the types are Send and Sync, as they were with Box links; and a MutItr
+    // over a Send but not Sync T (Cell) is still Send, like &mut T;
+    #[test]
+    fn test_send_sync() {
+        fn send_sync<T: Send + Sync>() {}
+        send_sync::<List<i32>>();
+        send_sync::<IntoItr<i32>>();
+        send_sync::<Itr<'_, i32>>();
+        send_sync::<MutItr<'_, i32>>();
+        send_sync::<CursorMut<'_, i32>>();
+
+        fn send<T: Send>() {}
+        send::<MutItr<'_, std::cell::Cell<i32>>>();
+        send::<CursorMut<'_, std::cell::Cell<i32>>>();
+    }
+
     #[test]
     fn test_drop_move() {
         let l: List<i32> = List::new();
@@ -317,8 +1390,10 @@ mod test {
         let _l = l; //OK!
 
         //3. List<T> can be partial mutated;
+        //   (not `l2.head.take()` any more, though: the links are Copy raw pointers, so that
+        //   would leave the nodes owned by nobody, i.e. leak them;)
         let mut l2: List<i32> = List::new();
-        l2.head.take();
+        l2.len = 0;
 
         //总之：实现Drop的类型
         //   1. 可以被move；
@@ -0,0 +1,607 @@
+use std::iter::{DoubleEndedIterator, IntoIterator, Iterator};
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+
+// A doubly linked deque: every node points to both of its neighbours, so a node in the middle
+// can be unlinked in O(1) just like the ends, once a cursor is on it (see CursorMut).
+//
+// A node has 2 pointers to it, and a Box can only have one owner; so all the links are raw
+// pointers, like in linkedlist1 (see the comments there). The deque owns every node, and turns
+// it back into a Box only to free it.
+pub struct Deque<T> {
+    head: Link<T>,
+    tail: Link<T>,
+    len: usize,
+    _owns: PhantomData<Box<Node<T>>>,
+}
+
+type Link<T> = Option<NonNull<Node<T>>>;
+
+struct Node<T> {
+    val: T,
+    prev: Link<T>,
+    next: Link<T>,
+}
+
+unsafe impl<T: Send> Send for Deque<T> {}
+unsafe impl<T: Sync> Sync for Deque<T> {}
+// the iterators and the cursor stand for &T (Itr), and &mut T (MutItr, CursorMut), the same as
+// std's linked_list ones;
+unsafe impl<'a, T: Sync> Send for Itr<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for Itr<'a, T> {}
+unsafe impl<'a, T: Send> Send for MutItr<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for MutItr<'a, T> {}
+unsafe impl<'a, T: Send> Send for CursorMut<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for CursorMut<'a, T> {}
+
+impl<T> Deque<T> {
+    pub fn new() -> Self {
+        Deque {
+            head: None,
+            tail: None,
+            len: 0,
+            _owns: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push_front(&mut self, val: T) {
+        self.link_between(val, None, self.head);
+    }
+
+    pub fn push_back(&mut self, val: T) {
+        self.link_between(val, self.tail, None);
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        // SAFETY: the head is a node of this deque;
+        self.head.map(|node| unsafe { self.unlink(node) })
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        // SAFETY: the tail is a node of this deque;
+        self.tail.map(|node| unsafe { self.unlink(node) })
+    }
+
+    // SAFETY of the 4 accessors below: the returned ref borrows the deque, so the node can
+    // neither be freed nor aliased mutably while the ref lives;
+    pub fn front(&self) -> Option<&T> {
+        self.head.map(|node| unsafe { &(*node.as_ptr()).val })
+    }
+
+    pub fn front_mut(&mut self) -> Option<&mut T> {
+        self.head.map(|node| unsafe { &mut (*node.as_ptr()).val })
+    }
+
+    pub fn back(&self) -> Option<&T> {
+        self.tail.map(|node| unsafe { &(*node.as_ptr()).val })
+    }
+
+    pub fn back_mut(&mut self) -> Option<&mut T> {
+        self.tail.map(|node| unsafe { &mut (*node.as_ptr()).val })
+    }
+
+    pub fn clear(&mut self) {
+        while self.pop_front().is_some() {}
+    }
+
+    pub fn itr(&self) -> Itr<'_, T> {
+        Itr {
+            front: self.head,
+            back: self.tail,
+            len: self.len,
+            _borrow: PhantomData,
+        }
+    }
+
+    pub fn mut_itr(&mut self) -> MutItr<'_, T> {
+        MutItr {
+            front: self.head,
+            back: self.tail,
+            len: self.len,
+            _borrow: PhantomData,
+        }
+    }
+
+    // a cursor on the first element (on the "ghost", see CursorMut, if the deque is empty);
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {
+            cur: self.head,
+            index: 0,
+            deque: self,
+        }
+    }
+
+    // a cursor on the last element (on the "ghost", see CursorMut, if the deque is empty);
+    pub fn cursor_back_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {
+            cur: self.tail,
+            index: self.len.saturating_sub(1),
+            deque: self,
+        }
+    }
+
+    // Put a new node between `prev` and `next`, which must be adjacent nodes of this deque;
+    // None stands for beyond the head (prev) or beyond the tail (next).
+    fn link_between(&mut self, val: T, prev: Link<T>, next: Link<T>) {
+        let node = NonNull::from(Box::leak(Box::new(Node { val, prev, next })));
+        // SAFETY: `prev` and `next` are nodes of this deque, and `&mut self` means nobody else
+        // is looking at them;
+        unsafe {
+            match prev {
+                Some(prev) => (*prev.as_ptr()).next = Some(node),
+                None => self.head = Some(node),
+            }
+            match next {
+                Some(next) => (*next.as_ptr()).prev = Some(node),
+                None => self.tail = Some(node),
+            }
+        }
+        self.len += 1;
+    }
+
+    // Take `node` out, linking its neighbours (or the ends) to each other, and free it.
+    // SAFETY: `node` must be a node of this deque, and nobody may hold a ref into it;
+    unsafe fn unlink(&mut self, node: NonNull<Node<T>>) -> T {
+        let node = Box::from_raw(node.as_ptr());
+        match node.prev {
+            Some(prev) => (*prev.as_ptr()).next = node.next,
+            None => self.head = node.next,
+        }
+        match node.next {
+            Some(next) => (*next.as_ptr()).prev = node.prev,
+            None => self.tail = node.prev,
+        }
+        self.len -= 1;
+        node.val
+    }
+}
+
+impl<T> Default for Deque<T> {
+    fn default() -> Self {
+        Deque::new()
+    }
+}
+
+impl<T> Drop for Deque<T> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+// Both ends move towards each other; `len` is what's left between them (inclusive), so they
+// stop when they meet, without comparing pointers.
+pub struct Itr<'a, T> {
+    front: Link<T>,
+    back: Link<T>,
+    len: usize,
+    _borrow: PhantomData<&'a T>,
+}
+
+impl<'a, T> Iterator for Itr<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.front.map(|node| {
+            self.len -= 1;
+            // SAFETY: the node is in the deque, which is borrowed shared for 'a;
+            let node = unsafe { &*node.as_ptr() };
+            self.front = node.next;
+            &node.val
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Itr<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.back.map(|node| {
+            self.len -= 1;
+            // SAFETY: see next();
+            let node = unsafe { &*node.as_ptr() };
+            self.back = node.prev;
+            &node.val
+        })
+    }
+}
+
+pub struct MutItr<'a, T> {
+    front: Link<T>,
+    back: Link<T>,
+    len: usize,
+    _borrow: PhantomData<&'a mut T>,
+}
+
+impl<'a, T> Iterator for MutItr<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.front.map(|node| {
+            self.len -= 1;
+            // SAFETY: the deque is borrowed mutably for 'a, and `len` makes sure each node is
+            // handed out once, from one end or the other;
+            let node = unsafe { &mut *node.as_ptr() };
+            self.front = node.next;
+            &mut node.val
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for MutItr<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.back.map(|node| {
+            self.len -= 1;
+            // SAFETY: see next();
+            let node = unsafe { &mut *node.as_ptr() };
+            self.back = node.prev;
+            &mut node.val
+        })
+    }
+}
+
+pub struct IntoItr<T> {
+    deque: Deque<T>,
+}
+
+impl<T> Iterator for IntoItr<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.deque.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.deque.len, Some(self.deque.len))
+    }
+}
+
+impl<T> DoubleEndedIterator for IntoItr<T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.deque.pop_back()
+    }
+}
+
+impl<T> IntoIterator for Deque<T> {
+    type Item = T;
+    type IntoIter = IntoItr<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoItr { deque: self }
+    }
+}
+
+// A cursor that can move both ways and edit the deque where it stands, all in O(1). Besides the
+// elements, it can be on a "ghost" position between the tail and the head: moving next from the
+// tail (or prev from the head) gets there, and moving on from the ghost wraps around to the
+// other end. On the ghost, `current()` is None, insert_after() inserts at the front and
+// insert_before() at the back.
+pub struct CursorMut<'a, T> {
+    // None for the ghost;
+    cur: Link<T>,
+    // index of `cur`, meaningless on the ghost;
+    index: usize,
+    deque: &'a mut Deque<T>,
+}
+
+impl<'a, T> CursorMut<'a, T> {
+    pub fn index(&self) -> Option<usize> {
+        self.cur.map(|_| self.index)
+    }
+
+    pub fn move_next(&mut self) {
+        match self.cur {
+            // SAFETY: `cur` is a node of the deque, which the cursor borrows mutably;
+            Some(node) => unsafe {
+                self.cur = (*node.as_ptr()).next;
+                self.index += 1;
+            },
+            None => {
+                self.cur = self.deque.head;
+                self.index = 0;
+            }
+        }
+    }
+
+    pub fn move_prev(&mut self) {
+        match self.cur {
+            // SAFETY: see move_next(); on the head `index` would go below 0, but the cursor is on
+            // the ghost then, and the index is not used;
+            Some(node) => unsafe {
+                self.cur = (*node.as_ptr()).prev;
+                self.index = self.index.wrapping_sub(1);
+            },
+            None => {
+                self.cur = self.deque.tail;
+                self.index = self.deque.len.saturating_sub(1);
+            }
+        }
+    }
+
+    // SAFETY of the 3 accessors below: the ref borrows the cursor mutably, so the cursor (the
+    // only way to the deque) can't move, edit, or hand out another ref while it lives;
+    pub fn current(&mut self) -> Option<&mut T> {
+        self.cur.map(|node| unsafe { &mut (*node.as_ptr()).val })
+    }
+
+    pub fn peek_next(&mut self) -> Option<&mut T> {
+        let next = match self.cur {
+            Some(node) => unsafe { (*node.as_ptr()).next },
+            None => self.deque.head,
+        };
+        next.map(|node| unsafe { &mut (*node.as_ptr()).val })
+    }
+
+    pub fn peek_prev(&mut self) -> Option<&mut T> {
+        let prev = match self.cur {
+            Some(node) => unsafe { (*node.as_ptr()).prev },
+            None => self.deque.tail,
+        };
+        prev.map(|node| unsafe { &mut (*node.as_ptr()).val })
+    }
+
+    pub fn insert_after(&mut self, val: T) {
+        match self.cur {
+            // SAFETY: see move_next();
+            Some(node) => {
+                let next = unsafe { (*node.as_ptr()).next };
+                self.deque.link_between(val, Some(node), next);
+            }
+            None => self.deque.push_front(val),
+        }
+    }
+
+    pub fn insert_before(&mut self, val: T) {
+        match self.cur {
+            // SAFETY: see move_next();
+            Some(node) => {
+                let prev = unsafe { (*node.as_ptr()).prev };
+                self.deque.link_between(val, prev, Some(node));
+                self.index += 1;
+            }
+            None => self.deque.push_back(val),
+        }
+    }
+
+    // Remove the current element, and move to the next one (which takes over the index); nothing
+    // to do on the ghost.
+    pub fn remove_current(&mut self) -> Option<T> {
+        let node = self.cur?;
+        // SAFETY: `node` is a node of the deque, and no ref into it can be alive, see current();
+        unsafe {
+            self.cur = (*node.as_ptr()).next;
+            Some(self.deque.unlink(node))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CursorMut, Deque, IntoItr, Itr, MutItr};
+
+    #[test]
+    fn basics() {
+        let mut d: Deque<i32> = Deque::new();
+        assert_eq!(d.pop_front(), None);
+        assert_eq!(d.pop_back(), None);
+        assert_eq!(d.front(), None);
+        assert_eq!(d.back(), None);
+
+        d.push_back(2);
+        d.push_back(3);
+        d.push_front(1);
+        d.push_front(0);
+        assert_eq!(d.len(), 4);
+        assert_eq!(d.front(), Some(&0));
+        assert_eq!(d.back(), Some(&3));
+
+        *d.front_mut().unwrap() = 10;
+        *d.back_mut().unwrap() = 30;
+        assert_eq!(d.pop_front(), Some(10));
+        assert_eq!(d.pop_back(), Some(30));
+        assert_eq!(d.pop_back(), Some(2));
+        assert_eq!(d.front(), Some(&1));
+        assert_eq!(d.back(), Some(&1));
+        assert_eq!(d.pop_front(), Some(1));
+        assert!(d.is_empty());
+        assert_eq!(d.back(), None);
+
+        d.push_front(5);
+        assert_eq!(d.back(), Some(&5));
+        assert_eq!(d.pop_back(), Some(5));
+        assert_eq!(d.front(), None);
+    }
+
+    #[test]
+    fn test_itr() {
+        let mut d: Deque<i32> = Deque::new();
+        for i in 1..=5 {
+            d.push_back(i);
+        }
+
+        let c: Vec<&i32> = d.itr().rev().collect();
+        assert_eq!(c, vec![&5, &4, &3, &2, &1]);
+
+        // the 2 ends meet in the middle;
+        let mut itr = d.itr();
+        assert_eq!(itr.next(), Some(&1));
+        assert_eq!(itr.next_back(), Some(&5));
+        assert_eq!(itr.next_back(), Some(&4));
+        assert_eq!(itr.size_hint(), (2, Some(2)));
+        assert_eq!(itr.next(), Some(&2));
+        assert_eq!(itr.next(), Some(&3));
+        assert_eq!(itr.next(), None);
+        assert_eq!(itr.next_back(), None);
+
+        let mut mut_itr = d.mut_itr();
+        *mut_itr.next_back().unwrap() *= 10;
+        for v in mut_itr {
+            *v += 100;
+        }
+        let c: Vec<i32> = d.itr().cloned().collect();
+        assert_eq!(c, vec![101, 102, 103, 104, 50]);
+
+        let mut into_itr = d.into_iter();
+        assert_eq!(into_itr.next_back(), Some(50));
+        assert_eq!(into_itr.next(), Some(101));
+        let c: Vec<i32> = into_itr.rev().collect();
+        assert_eq!(c, vec![104, 103, 102]);
+
+        let empty: Deque<i32> = Deque::default();
+        assert_eq!(empty.itr().next_back(), None);
+    }
+
+    #[test]
+    fn test_cursor() {
+        let mut d: Deque<i32> = Deque::new();
+        for i in 0..6 {
+            d.push_back(i);
+        }
+
+        let mut cursor = d.cursor_front_mut();
+        assert_eq!(cursor.index(), Some(0));
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(cursor.current(), Some(&mut 2));
+        assert_eq!(cursor.peek_prev(), Some(&mut 1));
+        assert_eq!(cursor.peek_next(), Some(&mut 3));
+
+        // remove in the middle, the cursor moves on to the next one;
+        assert_eq!(cursor.remove_current(), Some(2));
+        assert_eq!(cursor.current(), Some(&mut 3));
+        assert_eq!(cursor.index(), Some(2));
+
+        cursor.insert_before(20);
+        cursor.insert_after(30);
+        assert_eq!(cursor.index(), Some(3));
+        *cursor.current().unwrap() = 300;
+
+        // next from the tail is the ghost, and next from the ghost is the head;
+        cursor.move_next();
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(cursor.current(), Some(&mut 5));
+        cursor.move_next();
+        assert_eq!(cursor.index(), None);
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.peek_next(), Some(&mut 0));
+        assert_eq!(cursor.peek_prev(), Some(&mut 5));
+        assert_eq!(cursor.remove_current(), None);
+        cursor.insert_after(-1);
+        cursor.insert_before(6);
+        cursor.move_next();
+        assert_eq!(cursor.current(), Some(&mut -1));
+        cursor.move_prev();
+        cursor.move_prev();
+        assert_eq!(cursor.current(), Some(&mut 6));
+        assert_eq!(cursor.index(), Some(8));
+
+        // removing the tail puts the cursor on the ghost;
+        assert_eq!(cursor.remove_current(), Some(6));
+        assert_eq!(cursor.index(), None);
+
+        let c: Vec<i32> = d.itr().cloned().collect();
+        assert_eq!(c, vec![-1, 0, 1, 20, 300, 30, 4, 5]);
+        assert_eq!(d.len(), 8);
+        assert_eq!(d.back(), Some(&5));
+
+        // remove everything through a cursor from the back;
+        let mut cursor = d.cursor_back_mut();
+        assert_eq!(cursor.index(), Some(7));
+        while let Some(v) = cursor.current() {
+            let v = *v;
+            cursor.move_prev();
+            if v % 2 == 0 {
+                cursor.move_next();
+                cursor.remove_current();
+                cursor.move_prev();
+            }
+        }
+        let c: Vec<i32> = d.itr().cloned().collect();
+        assert_eq!(c, vec![-1, 1, 5]);
+
+        let mut empty: Deque<i32> = Deque::new();
+        let mut cursor = empty.cursor_back_mut();
+        assert_eq!(cursor.current(), None);
+        cursor.insert_before(1);
+        cursor.move_prev();
+        assert_eq!(cursor.current(), Some(&mut 1));
+        assert_eq!(cursor.index(), Some(0));
+    }
+
+    // A tiny LRU cache: most recently used at the front, evict from the back; a hit is moved to
+    // the front by unlinking it where it is.
+    #[test]
+    fn test_lru() {
+        let capacity = 3;
+        let mut lru: Deque<(u32, String)> = Deque::new();
+        let get = |lru: &mut Deque<(u32, String)>, key: u32| -> bool {
+            let mut cursor = lru.cursor_front_mut();
+            while let Some(entry) = cursor.current() {
+                if entry.0 == key {
+                    let entry = cursor.remove_current().unwrap();
+                    lru.push_front(entry);
+                    return true;
+                }
+                cursor.move_next();
+            }
+            if lru.len() == capacity {
+                lru.pop_back();
+            }
+            lru.push_front((key, key.to_string()));
+            false
+        };
+
+        let hits: Vec<bool> = [1, 2, 3, 1, 4, 2, 1, 3]
+            .iter()
+            .map(|k| get(&mut lru, *k))
+            .collect();
+        assert_eq!(
+            hits,
+            vec![false, false, false, true, false, false, true, false]
+        );
+        let keys: Vec<u32> = lru.itr().map(|e| e.0).collect();
+        assert_eq!(keys, vec![3, 1, 2]);
+    }
+
+    // Compiles only if the types are as thread safe as T is, like std's LinkedList and its
+    // iterators; a MutItr over a Send but not Sync T (Cell) is still Send, like &mut T;
+    #[test]
+    fn test_send_sync() {
+        fn send_sync<T: Send + Sync>() {}
+        send_sync::<Deque<i32>>();
+        send_sync::<IntoItr<i32>>();
+        send_sync::<Itr<'_, i32>>();
+        send_sync::<MutItr<'_, i32>>();
+        send_sync::<CursorMut<'_, i32>>();
+
+        fn send<T: Send>() {}
+        send::<MutItr<'_, std::cell::Cell<i32>>>();
+        send::<CursorMut<'_, std::cell::Cell<i32>>>();
+    }
+}
@@ -1,2 +1,8 @@
+pub mod concurrent;
+pub mod intrusive;
 pub mod linkedlist1;
 pub mod linkedlist2;
+pub mod linkedlist3;
+pub mod pooled;
+pub mod skiplist;
+pub mod unrolled;
@@ -0,0 +1,251 @@
+use std::marker::PhantomData;
+use std::mem::MaybeUninit;
+use std::ptr::NonNull;
+
+// A singly linked stack like linkedlist1::List, for push/pop heavy workloads: a popped node is
+// not freed but kept on a free list, and the next push takes it from there instead of going to
+// the allocator. Up to `pool_cap` nodes are kept; beyond that they are freed as usual, so a
+// burst doesn't pin its peak memory forever.
+//
+// A pooled node has no value, so `val` is MaybeUninit: initialized exactly while the node is in
+// the list, and uninitialized on the free list. The links are raw pointers, as in linkedlist1.
+pub struct PooledList<T> {
+    head: Link<T>,
+    len: usize,
+    free: Link<T>,
+    free_len: usize,
+    pool_cap: usize,
+    _owns: PhantomData<Box<Node<T>>>,
+}
+
+type Link<T> = Option<NonNull<Node<T>>>;
+
+struct Node<T> {
+    val: MaybeUninit<T>,
+    next: Link<T>,
+}
+
+// the pool cap of new();
+const DEFAULT_POOL_CAP: usize = 1024;
+
+unsafe impl<T: Send> Send for PooledList<T> {}
+unsafe impl<T: Sync> Sync for PooledList<T> {}
+
+impl<T> PooledList<T> {
+    pub fn new() -> Self {
+        PooledList::with_pool_cap(DEFAULT_POOL_CAP)
+    }
+
+    // keep at most `pool_cap` free nodes; 0 makes it a plain list;
+    pub fn with_pool_cap(pool_cap: usize) -> Self {
+        PooledList {
+            head: None,
+            len: 0,
+            free: None,
+            free_len: 0,
+            pool_cap,
+            _owns: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // the number of free nodes kept for reuse;
+    pub fn pool_len(&self) -> usize {
+        self.free_len
+    }
+
+    pub fn push_front(&mut self, val: T) {
+        let node = match self.free {
+            // SAFETY: free nodes are owned by the list, with no value in them;
+            Some(node) => unsafe {
+                self.free = (*node.as_ptr()).next;
+                self.free_len -= 1;
+                (*node.as_ptr()).val = MaybeUninit::new(val);
+                (*node.as_ptr()).next = self.head;
+                node
+            },
+            None => NonNull::from(Box::leak(Box::new(Node {
+                val: MaybeUninit::new(val),
+                next: self.head,
+            }))),
+        };
+        self.head = Some(node);
+        self.len += 1;
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        let node = self.head?;
+        // SAFETY: the node is in the list, so its value is initialized; it's read out exactly
+        // once, as the node goes to the free list (or is freed) right below;
+        unsafe {
+            self.head = (*node.as_ptr()).next;
+            self.len -= 1;
+            let val = (*node.as_ptr()).val.as_ptr().read();
+            self.recycle(node);
+            Some(val)
+        }
+    }
+
+    // SAFETY of the 2 accessors below: the ref borrows the list, so the node can neither be
+    // popped nor aliased mutably while the ref lives; a node in the list has a value;
+    pub fn front(&self) -> Option<&T> {
+        self.head
+            .map(|node| unsafe { &*(*node.as_ptr()).val.as_ptr() })
+    }
+
+    pub fn front_mut(&mut self) -> Option<&mut T> {
+        self.head
+            .map(|node| unsafe { &mut *(*node.as_ptr()).val.as_mut_ptr() })
+    }
+
+    pub fn itr(&self) -> Itr<'_, T> {
+        Itr {
+            curr: self.head,
+            _borrow: PhantomData,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        while self.pop_front().is_some() {}
+    }
+
+    // Free all the pooled nodes now;
+    pub fn shrink_pool(&mut self) {
+        while let Some(node) = self.free {
+            // SAFETY: a free node has no value to drop, so freeing the Box is all;
+            unsafe {
+                self.free = (*node.as_ptr()).next;
+                drop(Box::from_raw(node.as_ptr()));
+            }
+        }
+        self.free_len = 0;
+    }
+
+    // Put a node whose value has been moved out onto the free list, or free it if the pool is
+    // full.
+    // SAFETY: `node` must be unlinked from the list, with its value moved out;
+    unsafe fn recycle(&mut self, node: NonNull<Node<T>>) {
+        if self.free_len < self.pool_cap {
+            (*node.as_ptr()).next = self.free;
+            self.free = Some(node);
+            self.free_len += 1;
+        } else {
+            drop(Box::from_raw(node.as_ptr()));
+        }
+    }
+}
+
+impl<T> Default for PooledList<T> {
+    fn default() -> Self {
+        PooledList::new()
+    }
+}
+
+impl<T> Drop for PooledList<T> {
+    fn drop(&mut self) {
+        self.clear();
+        self.shrink_pool();
+    }
+}
+
+pub struct Itr<'a, T> {
+    curr: Link<T>,
+    _borrow: PhantomData<&'a T>,
+}
+
+impl<'a, T> Iterator for Itr<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.curr.map(|node| {
+            // SAFETY: the node is in the list, which is borrowed shared for 'a;
+            let node = unsafe { &*node.as_ptr() };
+            self.curr = node.next;
+            unsafe { &*node.val.as_ptr() }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::PooledList;
+    use std::rc::Rc;
+
+    #[test]
+    fn basics() {
+        let mut l: PooledList<i32> = PooledList::new();
+        assert_eq!(l.pop_front(), None);
+        assert_eq!(l.front(), None);
+
+        l.push_front(1);
+        l.push_front(2);
+        l.push_front(3);
+        assert_eq!(l.len(), 3);
+        assert_eq!(l.front(), Some(&3));
+        *l.front_mut().unwrap() = 30;
+        let c: Vec<&i32> = l.itr().collect();
+        assert_eq!(c, vec![&30, &2, &1]);
+
+        assert_eq!(l.pop_front(), Some(30));
+        assert_eq!(l.pop_front(), Some(2));
+        assert_eq!(l.pool_len(), 2);
+
+        // the pooled nodes are taken first;
+        l.push_front(4);
+        l.push_front(5);
+        assert_eq!(l.pool_len(), 0);
+        l.push_front(6);
+        let c: Vec<&i32> = l.itr().collect();
+        assert_eq!(c, vec![&6, &5, &4, &1]);
+        assert!(!l.is_empty());
+
+        l.clear();
+        assert!(l.is_empty());
+        assert_eq!(l.pool_len(), 4);
+        l.shrink_pool();
+        assert_eq!(l.pool_len(), 0);
+    }
+
+    #[test]
+    fn test_pool_cap() {
+        let mut l: PooledList<String> = PooledList::with_pool_cap(2);
+        for i in 0..5 {
+            l.push_front(i.to_string());
+        }
+        l.clear();
+        assert_eq!(l.pool_len(), 2);
+
+        let mut plain: PooledList<String> = PooledList::with_pool_cap(0);
+        plain.push_front(String::from("a"));
+        assert_eq!(plain.pop_front(), Some(String::from("a")));
+        assert_eq!(plain.pool_len(), 0);
+    }
+
+    // Every value is dropped exactly once: popped ones by the caller, the rest by the list, and
+    // never again from the pool.
+    #[test]
+    fn test_drops() {
+        let tracker = Rc::new(());
+        let mut l: PooledList<Rc<()>> = PooledList::with_pool_cap(3);
+        for _ in 0..10 {
+            l.push_front(Rc::clone(&tracker));
+        }
+        for _ in 0..6 {
+            l.pop_front();
+        }
+        assert_eq!(Rc::strong_count(&tracker), 5);
+        for _ in 0..2 {
+            l.push_front(Rc::clone(&tracker));
+        }
+        assert_eq!(Rc::strong_count(&tracker), 7);
+        drop(l);
+        assert_eq!(Rc::strong_count(&tracker), 1);
+    }
+}
@@ -0,0 +1,476 @@
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Bound, RangeBounds};
+use std::ptr::NonNull;
+
+use crate::prng::Prng;
+
+// A skip list (Pugh, 1990): a sorted linked list on level 0, plus sparser and sparser "express"
+// lists above it. Every node is on level 0, and on each level above with probability 1/2, so
+// level i links about n/2^i nodes; a search starts on the top level and drops a level each time
+// the next node would overshoot, which makes get, insert and remove O(log(n)) expected steps.
+// Like the treap, it's the coin flips that keep it balanced, not any rotation or rebuild: no
+// order of insertion can make it degenerate, only bad luck can, and that gets vanishingly
+// unlikely as n grows.
+pub struct SkipList<K, V> {
+    // head[i] is the first node on level i; the top level is the highest of any node;
+    head: Vec<Link<K, V>>,
+    len: usize,
+    rng: Prng,
+    _owns: PhantomData<Box<Node<K, V>>>,
+}
+
+type Link<K, V> = Option<NonNull<Node<K, V>>>;
+
+struct Node<K, V> {
+    key: K,
+    val: V,
+    // next[i] is the next node on level i; the node is on levels 0..next.len();
+    next: Box<[Link<K, V>]>,
+}
+
+const DEFAULT_SEED: u64 = 0x736b_6970;
+
+// 2^32 elements before the levels stop helping;
+const MAX_HEIGHT: usize = 32;
+
+unsafe impl<K: Send, V: Send> Send for SkipList<K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for SkipList<K, V> {}
+
+impl<K: Ord, V> SkipList<K, V> {
+    // the same seed gives the same levels, thus the same shape; see `with_seed`;
+    pub fn new() -> Self {
+        Self::with_seed(DEFAULT_SEED)
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        SkipList {
+            head: Vec::new(),
+            len: 0,
+            rng: Prng::new(seed),
+            _owns: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        // SAFETY: the node belongs to the list, and the ref borrows the list;
+        self.find(key).map(|node| unsafe { &(*node.as_ptr()).val })
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.find(key)
+            .map(|node| unsafe { &mut (*node.as_ptr()).val })
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.find(key).is_some()
+    }
+
+    // Insert a key-value pair; if the key exists, its value is replaced and the old one is
+    // returned, the same as std::collections::BTreeMap.
+    pub fn insert(&mut self, key: K, val: V) -> Option<V> {
+        let mut preds = self.preds(&key);
+        if let Some(node) = self.next(preds.first().cloned().flatten(), 0) {
+            // SAFETY: the node belongs to the list, and `&mut self` means nobody else is looking
+            // at it;
+            let node = unsafe { &mut *node.as_ptr() };
+            if node.key == key {
+                return Some(std::mem::replace(&mut node.val, val));
+            }
+        }
+
+        let height = self.random_height();
+        if height > self.head.len() {
+            // the new levels start out empty, so the head is the predecessor there;
+            self.head.resize(height, None);
+            preds.resize(height, None);
+        }
+        let next: Vec<Link<K, V>> = (0..height).map(|i| self.next(preds[i], i)).collect();
+        let node = NonNull::from(Box::leak(Box::new(Node {
+            key,
+            val,
+            next: next.into_boxed_slice(),
+        })));
+        for (i, pred) in preds.into_iter().take(height).enumerate() {
+            self.set_next(pred, i, Some(node));
+        }
+        self.len += 1;
+        None
+    }
+
+    // Remove a key, return its key-value pair;
+    pub fn remove(&mut self, key: &K) -> Option<(K, V)> {
+        let preds = self.preds(key);
+        let node = self.next(preds.first().cloned().flatten(), 0)?;
+        // SAFETY: the node belongs to the list; once it's unlinked from every level it's on,
+        // nothing points to it any more, and it goes back into its Box;
+        unsafe {
+            if node.as_ref().key != *key {
+                return None;
+            }
+            // on every level the node is on, it's the first one not before `key`, i.e. right
+            // after the predecessor;
+            for (i, next) in node.as_ref().next.iter().enumerate() {
+                self.set_next(preds[i], i, *next);
+            }
+            let node = Box::from_raw(node.as_ptr());
+            while let Some(None) = self.head.last() {
+                self.head.pop();
+            }
+            self.len -= 1;
+            Some((node.key, node.val))
+        }
+    }
+
+    // In-order iterator of the key-value pairs in a range, e.g. `skiplist.range(3..8)`; the same
+    // as BTree::range();
+    pub fn range<R>(&self, range: R) -> Iter<'_, K, V>
+    where
+        R: RangeBounds<K>,
+        K: Clone,
+    {
+        let end = match range.end_bound() {
+            Bound::Included(k) => Bound::Included(k.clone()),
+            Bound::Excluded(k) => Bound::Excluded(k.clone()),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        Iter {
+            next: self.seek(range.start_bound()),
+            end,
+            _borrow: PhantomData,
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            next: self.next(None, 0),
+            end: Bound::Unbounded,
+            _borrow: PhantomData,
+        }
+    }
+
+    // The number of nodes on each level, from level 0 (which has them all) up; about n/2^i on
+    // level i, which is what makes the searches logarithmic. O(n).
+    pub fn level_stats(&self) -> Vec<usize> {
+        (0..self.head.len())
+            .map(|i| {
+                let mut count = 0;
+                let mut node = self.head[i];
+                while let Some(n) = node {
+                    count += 1;
+                    // SAFETY: the node belongs to the list, which is borrowed;
+                    node = unsafe { n.as_ref() }.next[i];
+                }
+                count
+            })
+            .collect()
+    }
+
+    // 1 + the number of heads in a row, i.e. height h with probability 1/2^h;
+    fn random_height(&mut self) -> usize {
+        (self.rng.next_u64().trailing_ones() as usize + 1).min(MAX_HEIGHT)
+    }
+
+    // The next node after `pred` on `level`, where None for `pred` is the head (and a level
+    // above the top one is empty);
+    fn next(&self, pred: Link<K, V>, level: usize) -> Link<K, V> {
+        match pred {
+            // SAFETY: `pred` belongs to the list, and is on `level`;
+            Some(pred) => unsafe { pred.as_ref() }.next[level],
+            None => self.head.get(level).cloned().flatten(),
+        }
+    }
+
+    fn set_next(&mut self, pred: Link<K, V>, level: usize, link: Link<K, V>) {
+        match pred {
+            // SAFETY: as in next(), and `&mut self` means nobody else is looking at `pred`;
+            Some(pred) => unsafe { (*pred.as_ptr()).next[level] = link },
+            None => self.head[level] = link,
+        }
+    }
+
+    // The last node before `key` on every level, from the top down: what insert() and remove()
+    // relink. preds[i] is None if there's none, i.e. it's the head.
+    fn preds(&self, key: &K) -> Vec<Link<K, V>> {
+        let mut preds = vec![None; self.head.len()];
+        let mut pred = None;
+        for level in (0..self.head.len()).rev() {
+            // the walk goes on from where the level above stopped, which is the point;
+            while let Some(next) = self.next(pred, level) {
+                // SAFETY: as in next();
+                if unsafe { next.as_ref() }.key >= *key {
+                    break;
+                }
+                pred = Some(next);
+            }
+            preds[level] = pred;
+        }
+        preds
+    }
+
+    // The first node not before `start`, the same walk as preds() without keeping the way;
+    fn seek(&self, start: Bound<&K>) -> Link<K, V> {
+        let mut pred = None;
+        for level in (0..self.head.len()).rev() {
+            while let Some(next) = self.next(pred, level) {
+                // SAFETY: as in next();
+                let key = &unsafe { next.as_ref() }.key;
+                let before = match start {
+                    Bound::Included(start) => key < start,
+                    Bound::Excluded(start) => key <= start,
+                    Bound::Unbounded => false,
+                };
+                if !before {
+                    break;
+                }
+                pred = Some(next);
+            }
+        }
+        self.next(pred, 0)
+    }
+
+    fn find(&self, key: &K) -> Link<K, V> {
+        self.seek(Bound::Included(key))
+            // SAFETY: as in next();
+            .filter(|node| unsafe { node.as_ref() }.key == *key)
+    }
+}
+
+impl<K: Ord, V> Default for SkipList<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Level 0 links every node once, so it's all there is to free; iterative, so a long list can't
+// overflow the stack the way a recursive Drop of a Box chain could;
+impl<K, V> Drop for SkipList<K, V> {
+    fn drop(&mut self) {
+        let mut node = self.head.first().cloned().flatten();
+        while let Some(n) = node {
+            // SAFETY: each node is reached once, and freed after its next is read;
+            let n = unsafe { Box::from_raw(n.as_ptr()) };
+            node = n.next[0];
+        }
+    }
+}
+
+impl<K: Ord + fmt::Debug, V: fmt::Debug> fmt::Debug for SkipList<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+// Walks level 0 from the first node in range until past `end`;
+pub struct Iter<'a, K, V> {
+    next: Link<K, V>,
+    end: Bound<K>,
+    _borrow: PhantomData<&'a Node<K, V>>,
+}
+
+impl<'a, K: Ord, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // SAFETY: the node belongs to the list, which is borrowed shared for 'a;
+        let node = unsafe { &*self.next?.as_ptr() };
+        let past_end = match &self.end {
+            Bound::Included(end) => node.key > *end,
+            Bound::Excluded(end) => node.key >= *end,
+            Bound::Unbounded => false,
+        };
+        if past_end {
+            self.next = None;
+            return None;
+        }
+        self.next = node.next[0];
+        Some((&node.key, &node.val))
+    }
+}
+
+impl<'a, K: Ord, V> IntoIterator for &'a SkipList<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SkipList;
+    use crate::prng::Prng;
+    use std::collections::BTreeMap;
+    use std::ops::Bound;
+    use std::rc::Rc;
+
+    // check that every level is sorted and a sublist of the one below, return the levels' keys;
+    fn check<K: Ord + Clone, V>(l: &SkipList<K, V>) -> Vec<Vec<K>> {
+        let levels: Vec<Vec<K>> = (0..l.head.len())
+            .map(|i| {
+                let mut keys = vec![];
+                let mut node = l.head[i];
+                while let Some(n) = node {
+                    let n = unsafe { n.as_ref() };
+                    assert!(n.next.len() > i);
+                    keys.push(n.key.clone());
+                    node = n.next[i];
+                }
+                keys
+            })
+            .collect();
+        for (i, keys) in levels.iter().enumerate() {
+            assert!(keys.windows(2).all(|w| w[0] < w[1]));
+            assert!(!keys.is_empty(), "empty top level");
+            if i > 0 {
+                assert!(keys.iter().all(|k| levels[i - 1].binary_search(k).is_ok()));
+            }
+        }
+        assert_eq!(levels.first().map_or(0, |keys| keys.len()), l.len());
+        levels
+    }
+
+    #[test]
+    fn basics() {
+        let mut l: SkipList<i32, &str> = SkipList::new();
+        assert!(l.is_empty());
+        assert_eq!(l.get(&1), None);
+        assert_eq!(l.remove(&1), None);
+        assert_eq!(l.iter().next(), None);
+
+        assert_eq!(l.insert(5, "five"), None);
+        assert_eq!(l.insert(1, "one"), None);
+        assert_eq!(l.insert(3, "three"), None);
+        assert_eq!(l.insert(3, "THREE"), Some("three"));
+        assert_eq!(l.len(), 3);
+        assert_eq!(l.get(&3), Some(&"THREE"));
+        assert!(l.contains_key(&5));
+        assert!(!l.contains_key(&4));
+        *l.get_mut(&1).unwrap() = "ONE";
+        assert_eq!(format!("{:?}", l), r#"{1: "ONE", 3: "THREE", 5: "five"}"#);
+
+        assert_eq!(l.remove(&4), None);
+        assert_eq!(l.remove(&3), Some((3, "THREE")));
+        assert_eq!(l.remove(&3), None);
+        let keys: Vec<i32> = (&l).into_iter().map(|(k, _)| *k).collect();
+        assert_eq!(keys, vec![1, 5]);
+        check(&l);
+
+        l.remove(&1);
+        l.remove(&5);
+        assert!(l.is_empty());
+        assert!(l.head.is_empty());
+    }
+
+    // Random inserts and removes, against BTreeMap;
+    #[test]
+    fn test_random() {
+        let mut rng = Prng::new(11);
+        let mut l: SkipList<usize, usize> = SkipList::with_seed(5);
+        let mut model: BTreeMap<usize, usize> = BTreeMap::new();
+        let (rounds, keys) = if cfg!(miri) {
+            (500, 100)
+        } else {
+            (20_000, 2_000)
+        };
+        for round in 0..rounds {
+            let key = rng.gen_range(keys);
+            match rng.gen_range(3) {
+                0 | 1 => assert_eq!(l.insert(key, round), model.insert(key, round)),
+                _ => assert_eq!(l.remove(&key), model.remove(&key).map(|v| (key, v))),
+            }
+            let key = rng.gen_range(keys);
+            assert_eq!(l.get(&key), model.get(&key));
+        }
+        check(&l);
+        assert_eq!(l.len(), model.len());
+        assert!(l.iter().eq(model.iter()));
+    }
+
+    #[test]
+    fn test_range() {
+        let mut l: SkipList<i32, i32> = SkipList::new();
+        for k in 0..50 {
+            l.insert(k * 2, k);
+        }
+        let keys = |itr: super::Iter<'_, i32, i32>| itr.map(|(k, _)| *k).collect::<Vec<_>>();
+
+        assert_eq!(keys(l.range(10..16)), vec![10, 12, 14]);
+        assert_eq!(keys(l.range(9..=16)), vec![10, 12, 14, 16]);
+        assert_eq!(keys(l.range(95..)), vec![96, 98]);
+        assert_eq!(keys(l.range(..3)), vec![0, 2]);
+        assert_eq!(keys(l.range(40..40)), vec![]);
+        assert_eq!(keys(l.range(200..)), vec![]);
+        assert_eq!(
+            keys(l.range((Bound::Excluded(10), Bound::Included(14)))),
+            vec![12, 14]
+        );
+        assert_eq!(l.range(..).count(), 50);
+
+        let empty: SkipList<i32, i32> = SkipList::default();
+        assert_eq!(empty.range(1..).next(), None);
+    }
+
+    // Level i has about n/2^i nodes, and the same seed gives the same levels;
+    #[test]
+    fn test_level_stats() {
+        let n = if cfg!(miri) { 1 << 8 } else { 1 << 14 };
+        let build = |seed| {
+            let mut l = SkipList::with_seed(seed);
+            for k in 0..n {
+                l.insert(k, ());
+            }
+            l
+        };
+        let l = build(7);
+        let stats = l.level_stats();
+        let levels = check(&l);
+        assert_eq!(
+            stats,
+            levels.iter().map(|keys| keys.len()).collect::<Vec<_>>()
+        );
+        assert_eq!(stats[0], n);
+        // only the levels big enough for the count to be close to its expectation;
+        let big = stats.iter().enumerate().skip(1);
+        for (i, count) in big.take_while(|(i, _)| n >> i >= 512) {
+            let expected = (n >> i) as f64;
+            let off = (*count as f64 - expected).abs() / expected;
+            assert!(
+                off < 0.2,
+                "level {}: {} nodes, {} expected",
+                i,
+                count,
+                expected
+            );
+        }
+        assert_eq!(build(7).level_stats(), stats);
+        assert_ne!(build(8).level_stats(), stats);
+    }
+
+    // Every value is dropped exactly once: the replaced ones and the removed ones by the caller,
+    // the rest by the list;
+    #[test]
+    fn test_drops() {
+        let tracker = Rc::new(());
+        let mut l: SkipList<i32, Rc<()>> = SkipList::new();
+        for k in 0..100 {
+            l.insert(k % 60, Rc::clone(&tracker));
+        }
+        assert_eq!(Rc::strong_count(&tracker), 61);
+        for k in 0..10 {
+            l.remove(&k);
+        }
+        assert_eq!(Rc::strong_count(&tracker), 51);
+        drop(l);
+        assert_eq!(Rc::strong_count(&tracker), 1);
+    }
+}
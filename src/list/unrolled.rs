@@ -0,0 +1,740 @@
+use std::fmt;
+use std::iter::FromIterator;
+use std::marker::PhantomData;
+use std::mem::MaybeUninit;
+use std::ptr::{self, NonNull};
+use std::slice;
+
+// Elements per node. Bigger nodes mean fewer pointer hops (and fewer allocations) per element,
+// but more elements to shift on a positional insert or remove, and more wasted slots.
+const CAP: usize = 32;
+
+// A doubly linked list whose nodes hold up to CAP elements each, in an inline array, instead of
+// 1. A scan then mostly walks arrays, which is what caches and prefetchers like, and only hops
+// to another node every CAP elements; see src/bin/list_scan.rs.
+//
+// A node keeps its elements in a contiguous run of its slots, starting at `start`, which leaves
+// free slots on both sides of the run: push and pop at both ends are O(1), and a positional
+// insert or remove shifts at most CAP/2 elements. A full node is split in 2 halves to make room,
+// and a node that gets below half full after a remove is merged into a neighbour if they fit in
+// one node, so the nodes don't thin out.
+pub struct UnrolledList<T> {
+    head: Link<T>,
+    tail: Link<T>,
+    len: usize,
+    _owns: PhantomData<Box<Node<T>>>,
+}
+
+type Link<T> = Option<NonNull<Node<T>>>;
+
+// Never empty while it's in a list;
+struct Node<T> {
+    // vals[start..start + len] are initialized, the others aren't;
+    vals: [MaybeUninit<T>; CAP],
+    start: usize,
+    len: usize,
+    prev: Link<T>,
+    next: Link<T>,
+}
+
+impl<T> Node<T> {
+    // An empty node whose run starts at `start`: 0 for a node that will grow to the back, CAP for
+    // one that will grow to the front;
+    fn new(start: usize) -> Box<Self> {
+        Box::new(Node {
+            vals: [(); CAP].map(|_| MaybeUninit::uninit()),
+            start,
+            len: 0,
+            prev: None,
+            next: None,
+        })
+    }
+
+    fn is_full(&self) -> bool {
+        self.len == CAP
+    }
+
+    // Slot 0. Take it once per operation, and do all the pointer work from it: taking it again
+    // reborrows `vals`, which would invalidate the pointers taken before (as far as Miri's
+    // Stacked Borrows are concerned).
+    fn base(&mut self) -> *mut T {
+        self.vals.as_mut_ptr() as *mut T
+    }
+
+    fn as_slice(&self) -> &[T] {
+        // SAFETY: the run is initialized;
+        unsafe { slice::from_raw_parts((self.vals.as_ptr() as *const T).add(self.start), self.len) }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        let start = self.start;
+        // SAFETY: the run is initialized;
+        unsafe { slice::from_raw_parts_mut(self.base().add(start), self.len) }
+    }
+
+    // Insert `val` at offset `i` of the run, shifting the elements on the side that has room (the
+    // shorter side if both have);
+    fn insert(&mut self, i: usize, val: T) {
+        assert!(i <= self.len && !self.is_full());
+        let shift_front = self.start > 0 && (self.start + self.len == CAP || i < self.len - i);
+        let base = self.base();
+        // SAFETY: the shifts stay in the array, since there's a free slot on the side they go to,
+        // and the slot `val` is written to is the one they freed;
+        unsafe {
+            if shift_front {
+                self.start -= 1;
+                let first = base.add(self.start);
+                ptr::copy(first.add(1), first, i);
+                first.add(i).write(val);
+            } else {
+                let at = base.add(self.start + i);
+                ptr::copy(at, at.add(1), self.len - i);
+                at.write(val);
+            }
+        }
+        self.len += 1;
+    }
+
+    // Move the element at offset `i` of the run out, closing the gap from the shorter side, which
+    // makes it O(1) at both ends;
+    fn remove(&mut self, i: usize) -> T {
+        assert!(i < self.len);
+        let base = self.base();
+        // SAFETY: `i` is in the run; the slot it's read from is overwritten by the shift or left
+        // out of the run;
+        unsafe {
+            let first = base.add(self.start);
+            let val = first.add(i).read();
+            if i < self.len - 1 - i {
+                ptr::copy(first, first.add(1), i);
+                self.start += 1;
+            } else {
+                ptr::copy(first.add(i + 1), first.add(i), self.len - 1 - i);
+            }
+            self.len -= 1;
+            val
+        }
+    }
+
+    // Move the elements from offset `at` of the run to a new node, at the front of its array;
+    fn split_off(&mut self, at: usize) -> Box<Self> {
+        assert!(at <= self.len);
+        let mut other = Node::new(0);
+        let n = self.len - at;
+        let base = self.base();
+        // SAFETY: the elements are moved, and left out of this node's run;
+        unsafe { ptr::copy_nonoverlapping(base.add(self.start + at), other.base(), n) }
+        other.len = n;
+        self.len = at;
+        other
+    }
+
+    // Move all the elements of `other` to the end of this node's run;
+    fn append(&mut self, other: &mut Self) {
+        assert!(self.len + other.len <= CAP);
+        if self.start + self.len + other.len > CAP {
+            // slide the run to the front of the array, to make room after it;
+            let base = self.base();
+            // SAFETY: the run stays in the array, and the slots it leaves are out of it;
+            unsafe { ptr::copy(base.add(self.start), base, self.len) }
+            self.start = 0;
+        }
+        let (dst, src) = (self.base(), other.base());
+        // SAFETY: as in split_off();
+        unsafe {
+            ptr::copy_nonoverlapping(
+                src.add(other.start),
+                dst.add(self.start + self.len),
+                other.len,
+            )
+        }
+        self.len += other.len;
+        other.len = 0;
+    }
+}
+
+impl<T> Drop for Node<T> {
+    fn drop(&mut self) {
+        // SAFETY: the run is initialized, and is never looked at again;
+        unsafe { ptr::drop_in_place(self.as_mut_slice()) }
+    }
+}
+
+unsafe impl<T: Send> Send for UnrolledList<T> {}
+unsafe impl<T: Sync> Sync for UnrolledList<T> {}
+
+impl<T> UnrolledList<T> {
+    pub fn new() -> Self {
+        UnrolledList {
+            head: None,
+            tail: None,
+            len: 0,
+            _owns: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push_front(&mut self, val: T) {
+        let head = match self.head {
+            // SAFETY (here and below): the nodes belong to the list, and `&mut self` means nobody
+            // else is looking at them;
+            Some(head) if !unsafe { head.as_ref() }.is_full() => head,
+            _ => self.link_after(None, Node::new(CAP)),
+        };
+        unsafe { (*head.as_ptr()).insert(0, val) };
+        self.len += 1;
+    }
+
+    pub fn push_back(&mut self, val: T) {
+        let tail = match self.tail {
+            Some(tail) if !unsafe { tail.as_ref() }.is_full() => tail,
+            _ => self.link_after(self.tail, Node::new(0)),
+        };
+        unsafe {
+            let len = tail.as_ref().len;
+            (*tail.as_ptr()).insert(len, val);
+        }
+        self.len += 1;
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        let head = self.head?;
+        // SAFETY: as in push_front();
+        unsafe { Some(self.remove_from(head, 0)) }
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        let tail = self.tail?;
+        unsafe {
+            let last = tail.as_ref().len - 1;
+            Some(self.remove_from(tail, last))
+        }
+    }
+
+    // SAFETY of the accessors below: the ref borrows the list, so the node can't go away, nor be
+    // aliased mutably, while the ref lives; and a node in the list is never empty;
+    pub fn front(&self) -> Option<&T> {
+        self.head
+            .map(|head| &unsafe { &*head.as_ptr() }.as_slice()[0])
+    }
+
+    pub fn front_mut(&mut self) -> Option<&mut T> {
+        self.head
+            .map(|head| &mut unsafe { &mut *head.as_ptr() }.as_mut_slice()[0])
+    }
+
+    pub fn back(&self) -> Option<&T> {
+        self.tail
+            .and_then(|tail| unsafe { &*tail.as_ptr() }.as_slice().last())
+    }
+
+    pub fn back_mut(&mut self) -> Option<&mut T> {
+        self.tail
+            .and_then(|tail| unsafe { &mut *tail.as_ptr() }.as_mut_slice().last_mut())
+    }
+
+    // O(index / CAP), from whichever end is nearer;
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if index >= self.len {
+            return None;
+        }
+        let (node, i) = self.locate(index);
+        Some(&unsafe { &*node.as_ptr() }.as_slice()[i])
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        if index >= self.len {
+            return None;
+        }
+        let (node, i) = self.locate(index);
+        Some(&mut unsafe { &mut *node.as_ptr() }.as_mut_slice()[i])
+    }
+
+    // Insert `val` so that it ends up at `index`, splitting its node if it's full.
+    // Panics if index > len;
+    pub fn insert(&mut self, index: usize, val: T) {
+        assert!(index <= self.len, "index out of range");
+        // the ends grow their own way, with a new node rather than a split;
+        if index == 0 {
+            return self.push_front(val);
+        }
+        if index == self.len {
+            return self.push_back(val);
+        }
+        let (node, i) = self.locate(index);
+        // SAFETY: as in push_front();
+        unsafe {
+            if node.as_ref().is_full() {
+                let other = (*node.as_ptr()).split_off(CAP / 2);
+                let other = self.link_after(Some(node), other);
+                if i <= CAP / 2 {
+                    (*node.as_ptr()).insert(i, val);
+                } else {
+                    (*other.as_ptr()).insert(i - CAP / 2, val);
+                }
+            } else {
+                (*node.as_ptr()).insert(i, val);
+            }
+        }
+        self.len += 1;
+    }
+
+    // Move the element at `index` out, or None if index >= len;
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        if index >= self.len {
+            return None;
+        }
+        let (node, i) = self.locate(index);
+        // SAFETY: as in push_front();
+        unsafe {
+            let val = (*node.as_ptr()).remove(i);
+            self.len -= 1;
+            self.merge(node);
+            Some(val)
+        }
+    }
+
+    pub fn clear(&mut self) {
+        while let Some(head) = self.head {
+            // SAFETY: the head belongs to the list;
+            drop(unsafe { self.unlink(head) });
+        }
+        self.len = 0;
+    }
+
+    pub fn itr(&self) -> Itr<'_, T> {
+        Itr {
+            vals: Default::default(),
+            next: self.head,
+            len: self.len,
+        }
+    }
+
+    pub fn mut_itr(&mut self) -> MutItr<'_, T> {
+        MutItr {
+            vals: Default::default(),
+            next: self.head,
+            len: self.len,
+        }
+    }
+
+    // The node holding the element at `index` (which must be < len), and the element's offset in
+    // its run;
+    fn locate(&self, index: usize) -> (NonNull<Node<T>>, usize) {
+        debug_assert!(index < self.len);
+        // SAFETY: the nodes belong to the list, and their lens add up to self.len, so the walk
+        // stops before running off the end;
+        unsafe {
+            if index < self.len / 2 {
+                let (mut node, mut i) = (self.head.unwrap(), index);
+                while i >= node.as_ref().len {
+                    i -= node.as_ref().len;
+                    node = node.as_ref().next.unwrap();
+                }
+                (node, i)
+            } else {
+                // count from the back: `i` elements after the one we want;
+                let (mut node, mut i) = (self.tail.unwrap(), self.len - 1 - index);
+                while i >= node.as_ref().len {
+                    i -= node.as_ref().len;
+                    node = node.as_ref().prev.unwrap();
+                }
+                (node, node.as_ref().len - 1 - i)
+            }
+        }
+    }
+
+    // Remove the element at offset `i` of `node`, and free the node if that empties it; for the
+    // ends, which don't merge.
+    // SAFETY: `node` must belong to the list, and nobody may hold a ref into it;
+    unsafe fn remove_from(&mut self, node: NonNull<Node<T>>, i: usize) -> T {
+        let val = (*node.as_ptr()).remove(i);
+        if node.as_ref().len == 0 {
+            drop(self.unlink(node));
+        }
+        self.len -= 1;
+        val
+    }
+
+    // After a remove from `node`: free it if it's empty; if it's below half full, merge the next
+    // node into it, or it into the previous one, whichever fits in one node.
+    // SAFETY: as in remove_from();
+    unsafe fn merge(&mut self, node: NonNull<Node<T>>) {
+        let len = node.as_ref().len;
+        if len == 0 {
+            drop(self.unlink(node));
+        } else if len < CAP / 2 {
+            let (prev, next) = (node.as_ref().prev, node.as_ref().next);
+            match (prev, next) {
+                (_, Some(next)) if len + next.as_ref().len <= CAP => {
+                    (*node.as_ptr()).append(&mut *next.as_ptr());
+                    drop(self.unlink(next));
+                }
+                (Some(prev), _) if prev.as_ref().len + len <= CAP => {
+                    (*prev.as_ptr()).append(&mut *node.as_ptr());
+                    drop(self.unlink(node));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Link `node` in after `prev`, or at the front for None;
+    fn link_after(&mut self, prev: Link<T>, node: Box<Node<T>>) -> NonNull<Node<T>> {
+        let node = NonNull::from(Box::leak(node));
+        // SAFETY: `prev` and its next belong to the list, and `&mut self` means nobody else is
+        // looking at them;
+        unsafe {
+            let next = match prev {
+                Some(prev) => prev.as_ref().next,
+                None => self.head,
+            };
+            (*node.as_ptr()).prev = prev;
+            (*node.as_ptr()).next = next;
+            match prev {
+                Some(prev) => (*prev.as_ptr()).next = Some(node),
+                None => self.head = Some(node),
+            }
+            match next {
+                Some(next) => (*next.as_ptr()).prev = Some(node),
+                None => self.tail = Some(node),
+            }
+        }
+        node
+    }
+
+    // Take `node` out, linking its neighbours (or the ends) to each other; the elements left in
+    // it, if any, go with it. self.len is the caller's business.
+    // SAFETY: `node` must belong to the list, and nobody may hold a ref into it;
+    unsafe fn unlink(&mut self, node: NonNull<Node<T>>) -> Box<Node<T>> {
+        let node = Box::from_raw(node.as_ptr());
+        match node.prev {
+            Some(prev) => (*prev.as_ptr()).next = node.next,
+            None => self.head = node.next,
+        }
+        match node.next {
+            Some(next) => (*next.as_ptr()).prev = node.prev,
+            None => self.tail = node.prev,
+        }
+        node
+    }
+}
+
+impl<T> Default for UnrolledList<T> {
+    fn default() -> Self {
+        UnrolledList::new()
+    }
+}
+
+impl<T> Drop for UnrolledList<T> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for UnrolledList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.itr()).finish()
+    }
+}
+
+impl<T> Extend<T> for UnrolledList<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for val in iter {
+            self.push_back(val);
+        }
+    }
+}
+
+impl<T> FromIterator<T> for UnrolledList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut list = UnrolledList::new();
+        list.extend(iter);
+        list
+    }
+}
+
+// The iterators walk the run of a node as a slice, and only go to the next node when it's done;
+// `len` is what's left in all, for size_hint().
+pub struct Itr<'a, T> {
+    vals: slice::Iter<'a, T>,
+    next: Link<T>,
+    len: usize,
+}
+
+impl<'a, T> Iterator for Itr<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(val) = self.vals.next() {
+                self.len -= 1;
+                return Some(val);
+            }
+            // SAFETY: the node belongs to the list, which is borrowed shared for 'a;
+            let node = unsafe { &*self.next?.as_ptr() };
+            self.next = node.next;
+            self.vals = node.as_slice().iter();
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+pub struct MutItr<'a, T> {
+    vals: slice::IterMut<'a, T>,
+    next: Link<T>,
+    len: usize,
+}
+
+impl<'a, T> Iterator for MutItr<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(val) = self.vals.next() {
+                self.len -= 1;
+                return Some(val);
+            }
+            // SAFETY: the node belongs to the list, which is borrowed mutably for 'a, and each
+            // node is visited once;
+            let node = unsafe { &mut *self.next?.as_ptr() };
+            self.next = node.next;
+            self.vals = node.as_mut_slice().iter_mut();
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+pub struct IntoItr<T> {
+    list: UnrolledList<T>,
+}
+
+impl<T> Iterator for IntoItr<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.list.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.list.len, Some(self.list.len))
+    }
+}
+
+impl<T> IntoIterator for UnrolledList<T> {
+    type Item = T;
+    type IntoIter = IntoItr<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoItr { list: self }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a UnrolledList<T> {
+    type Item = &'a T;
+    type IntoIter = Itr<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.itr()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut UnrolledList<T> {
+    type Item = &'a mut T;
+    type IntoIter = MutItr<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.mut_itr()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{UnrolledList, CAP};
+    use crate::prng::Prng;
+    use std::rc::Rc;
+
+    // The lens of the nodes as they are linked both ways, checking the links and the runs on
+    // the way;
+    fn node_lens<T>(l: &UnrolledList<T>) -> Vec<usize> {
+        let mut lens = vec![];
+        let mut prev = None;
+        let mut node = l.head;
+        while let Some(n) = node {
+            let n = unsafe { n.as_ref() };
+            assert_eq!(n.prev, prev);
+            assert!(n.len > 0 && n.start + n.len <= CAP);
+            lens.push(n.len);
+            prev = node;
+            node = n.next;
+        }
+        assert_eq!(l.tail, prev);
+        assert_eq!(lens.iter().sum::<usize>(), l.len());
+        lens
+    }
+
+    fn to_vec<T: Clone>(l: &UnrolledList<T>) -> Vec<T> {
+        l.itr().cloned().collect()
+    }
+
+    #[test]
+    fn basics() {
+        let mut l: UnrolledList<i32> = UnrolledList::new();
+        assert_eq!(l.pop_front(), None);
+        assert_eq!(l.pop_back(), None);
+        assert_eq!(l.front(), None);
+        assert_eq!(l.back(), None);
+
+        for i in 0..100 {
+            l.push_back(i);
+            l.push_front(-i - 1);
+        }
+        assert_eq!(l.len(), 200);
+        assert_eq!(to_vec(&l), (-100..100).collect::<Vec<_>>());
+        // the first node took both ends until full, then each end grew its own nodes;
+        assert_eq!(node_lens(&l), vec![20, CAP, CAP, CAP, CAP, CAP, 20]);
+
+        *l.front_mut().unwrap() *= 10;
+        *l.back_mut().unwrap() *= 10;
+        assert_eq!((l.front(), l.back()), (Some(&-1000), Some(&990)));
+
+        for i in 0..150 {
+            let expected = if i == 0 { -1000 } else { i - 100 };
+            assert_eq!(l.get(0), Some(&expected));
+            assert_eq!(l.pop_front(), Some(expected));
+        }
+        node_lens(&l);
+        assert_eq!(l.pop_back(), Some(990));
+        assert_eq!(l.pop_back(), Some(98));
+        assert_eq!(to_vec(&l), (50..98).collect::<Vec<_>>());
+        assert_eq!(l.get(48), None);
+
+        l.clear();
+        assert!(l.is_empty());
+        assert_eq!(node_lens(&l), vec![]);
+        l.push_back(1);
+        assert_eq!(l.pop_front(), Some(1));
+    }
+
+    #[test]
+    fn test_split_merge() {
+        let mut l: UnrolledList<usize> = (0..CAP).collect();
+        assert_eq!(node_lens(&l), vec![CAP]);
+
+        // a full node splits in halves;
+        l.insert(5, 100);
+        assert_eq!(node_lens(&l), vec![CAP / 2 + 1, CAP / 2]);
+        l.insert(CAP - 2, 200);
+        assert_eq!(node_lens(&l), vec![CAP / 2 + 1, CAP / 2 + 1]);
+        let mut expected: Vec<usize> = (0..CAP).collect();
+        expected.insert(5, 100);
+        expected.insert(CAP - 2, 200);
+        assert_eq!(to_vec(&l), expected);
+
+        // and merges back once a half gets below half full;
+        assert_eq!(l.remove(5), Some(100));
+        assert_eq!(node_lens(&l), vec![CAP / 2, CAP / 2 + 1]);
+        assert_eq!(l.remove(0), Some(0));
+        assert_eq!(node_lens(&l), vec![CAP]);
+        expected.retain(|&v| v > 0 && v != 100);
+        assert_eq!(to_vec(&l), expected);
+
+        assert_eq!(l.remove(CAP), None);
+    }
+
+    // Random inserts and removes everywhere, against a Vec;
+    #[test]
+    fn test_random() {
+        let mut rng = Prng::new(3);
+        let mut l: UnrolledList<u64> = UnrolledList::new();
+        let mut model: Vec<u64> = vec![];
+        let rounds = if cfg!(miri) { 300 } else { 20_000 };
+        for round in 0..rounds {
+            // more inserts first, more removes later, so that the list grows and shrinks;
+            let grow = if round < rounds / 2 { 3 } else { 1 };
+            if rng.gen_range(4) < grow {
+                let at = rng.gen_range(model.len() + 1);
+                let val = rng.next_u64();
+                l.insert(at, val);
+                model.insert(at, val);
+            } else {
+                let at = rng.gen_range(model.len() + 1);
+                let expected = if at < model.len() {
+                    Some(model.remove(at))
+                } else {
+                    None
+                };
+                assert_eq!(l.remove(at), expected);
+            }
+            if round % 97 == 0 {
+                node_lens(&l);
+                assert_eq!(to_vec(&l), model);
+            }
+        }
+        assert_eq!(to_vec(&l), model);
+        for (i, v) in model.iter().enumerate() {
+            assert_eq!(l.get(i), Some(v));
+        }
+    }
+
+    #[test]
+    fn test_itr() {
+        let mut l: UnrolledList<i32> = (0..100).collect();
+        let mut itr = l.itr();
+        assert_eq!(itr.size_hint(), (100, Some(100)));
+        itr.nth(40);
+        assert_eq!(itr.size_hint(), (59, Some(59)));
+        assert_eq!(itr.next(), Some(&41));
+
+        for v in l.mut_itr() {
+            *v *= 2;
+        }
+        for v in &mut l {
+            *v += 1;
+        }
+        let c: Vec<i32> = (&l).into_iter().cloned().collect();
+        assert_eq!(c, (0..100).map(|v| 2 * v + 1).collect::<Vec<_>>());
+        *l.get_mut(3).unwrap() = 0;
+        assert_eq!(format!("{:?}", l).get(..14), Some("[1, 3, 5, 0, 9"));
+
+        let mut into = l.into_iter();
+        assert_eq!(into.next(), Some(1));
+        assert_eq!(into.size_hint(), (99, Some(99)));
+        assert_eq!(into.sum::<i32>(), 100 * 100 - 1 - 7);
+
+        let empty: UnrolledList<i32> = UnrolledList::default();
+        assert_eq!(empty.itr().next(), None);
+    }
+
+    // Every element is dropped exactly once: by whoever took it out, or by the list; splits and
+    // merges move elements without dropping them.
+    #[test]
+    fn test_drops() {
+        let tracker = Rc::new(());
+        let mut l: UnrolledList<Rc<()>> = UnrolledList::new();
+        for i in 0..3 * CAP {
+            l.insert(i / 2, Rc::clone(&tracker));
+        }
+        for i in 0..CAP {
+            l.remove(i);
+        }
+        assert_eq!(Rc::strong_count(&tracker), 2 * CAP + 1);
+        let mut into = l.into_iter();
+        into.next();
+        drop(into);
+        assert_eq!(Rc::strong_count(&tracker), 1);
+    }
+}
@@ -0,0 +1,83 @@
+// A tiny deterministic pseudo random number generator (xorshift64*), so that the examples and
+// tests can use randomness without pulling in any dependency, and can reproduce a run by seed.
+//
+// See https://en.wikipedia.org/wiki/Xorshift#xorshift*
+pub struct Prng {
+    state: u64,
+}
+
+impl Prng {
+    // xorshift gets stuck at 0 forever, so a 0 seed is replaced by a fixed non-zero constant;
+    pub fn new(seed: u64) -> Self {
+        let state = if seed == 0 { 0x9E37_79B9_7F4A_7C15 } else { seed };
+        Prng { state }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    // uniform in [0, 1); the top 53 bits are exactly what an f64 mantissa can hold;
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // uniform in [0, n); panics if n is 0;
+    pub fn gen_range(&mut self, n: usize) -> usize {
+        assert!(n > 0, "gen_range: empty range");
+        (self.next_u64() % n as u64) as usize
+    }
+
+    // Fisher-Yates shuffle;
+    pub fn shuffle<T>(&mut self, v: &mut [T]) {
+        for i in (1..v.len()).rev() {
+            let j = self.gen_range(i + 1);
+            v.swap(i, j);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Prng;
+
+    #[test]
+    fn test_reproducible() {
+        let mut a = Prng::new(42);
+        let mut b = Prng::new(42);
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+
+        let mut c = Prng::new(43);
+        let xs: Vec<u64> = (0..10).map(|_| a.next_u64()).collect();
+        let ys: Vec<u64> = (0..10).map(|_| c.next_u64()).collect();
+        assert_ne!(xs, ys);
+    }
+
+    #[test]
+    fn test_range() {
+        let mut r = Prng::new(0);
+        let mut hits = [0usize; 10];
+        for _ in 0..10000 {
+            let x = r.gen_range(10);
+            hits[x] += 1;
+            let f = r.next_f64();
+            assert!((0.0..1.0).contains(&f));
+        }
+        for h in hits.iter() {
+            assert!(*h > 800 && *h < 1200, "{:?}", hits);
+        }
+
+        let mut v: Vec<i32> = (0..100).collect();
+        r.shuffle(&mut v);
+        let mut sorted = v.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..100).collect::<Vec<i32>>());
+    }
+}
@@ -1,21 +1,30 @@
-use std::collections::LinkedList;
-use std::ops::Deref;
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
 
 pub struct Node<T> {
-    val: T,
-    left: Link<T>,
-    right: Link<T>,
+    pub(crate) val: T,
+    pub(crate) left: Link<T>,
+    pub(crate) right: Link<T>,
 }
 
-type Link<T> = Option<Box<Node<T>>>;
+pub(crate) type Link<T> = Option<Box<Node<T>>>;
 
 pub struct BSTree<T> {
     root: Link<T>,
+    // nodes are never removed, so it only grows; the traversals size their stacks from it;
+    height: usize,
 }
 
 impl<T: Ord> BSTree<T> {
     pub fn new() -> Self {
-        BSTree { root: None }
+        BSTree {
+            root: None,
+            height: 0,
+        }
     }
 
     pub fn insert(&mut self, val: T) {
@@ -27,6 +36,7 @@ impl<T: Ord> BSTree<T> {
         //      in C/C++);
 
         let mut target_place: &mut Link<T> = &mut self.root;
+        let mut depth = 1;
 
         //`while let` is a shortcut for match; here we are actually matching `target_place` (a ref)
         // with `Some(boxed_node)` (a value), so `boxed_node` is a (mut) ref to the Box<Node<T>>
@@ -37,7 +47,8 @@ impl<T: Ord> BSTree<T> {
                 &mut boxed_node.left
             } else {
                 &mut boxed_node.right
-            }
+            };
+            depth += 1;
         }
 
         //modify a value by its pointer/ref, the same as what we do in C/C++;
@@ -46,190 +57,676 @@ impl<T: Ord> BSTree<T> {
             left: None,
             right: None,
         }));
+        self.height = self.height.max(depth);
+    }
+
+    // Build a perfectly balanced tree from sorted values in O(n), see build_balanced();
+    pub fn from_sorted(vals: Vec<T>) -> Self {
+        debug_assert!(vals.windows(2).all(|w| w[0] <= w[1]));
+        let n = vals.len();
+        let mut nodes = vals.into_iter().map(|val| {
+            Box::new(Node {
+                val,
+                left: None,
+                right: None,
+            })
+        });
+        BSTree {
+            root: build_balanced(&mut nodes, n),
+            // the right subtree gets the extra node when n-1 is odd, so the height is the number
+            // of bits of n;
+            height: (usize::BITS - n.leading_zeros()) as usize,
+        }
+    }
+
+    // Rebuild the exact tree from its preorder and inorder sequences, e.g. the outputs of
+    // iter(Order::Pre) and iter(Order::In); see rebuild();
+    //
+    // With duplicates, only a tree built by insert() round-trips: equal values are taken to be
+    // in the left subtree of each other, as insert() puts them. A tree with an equal value on the
+    // right, which from_sorted() may build (e.g. from [1, 5, 5]), gives Mismatch, or another tree
+    // with the same inorder content.
+    pub fn from_preorder_inorder(preorder: Vec<T>, inorder: &[T]) -> Result<Self, RebuildError> {
+        check_sequences(preorder.len(), inorder)?;
+        let root = rebuild(&mut preorder.into_iter(), inorder, false)?;
+        let height = height(&root);
+        Ok(BSTree { root, height })
+    }
+
+    // The same as from_preorder_inorder(), but from the postorder sequence: read backwards, it's
+    // the root first, then the right subtree, then the left subtree. Duplicates are taken the
+    // same way, with the same caveat;
+    pub fn from_postorder_inorder(postorder: Vec<T>, inorder: &[T]) -> Result<Self, RebuildError> {
+        check_sequences(postorder.len(), inorder)?;
+        let root = rebuild(&mut postorder.into_iter().rev(), inorder, true)?;
+        let height = height(&root);
+        Ok(BSTree { root, height })
+    }
+
+    pub fn get(&self, val: &T) -> Option<&T> {
+        let mut link: &Link<T> = &self.root;
+        while let Some(boxed_node) = link {
+            link = match val.cmp(&boxed_node.val) {
+                Ordering::Less => &boxed_node.left,
+                Ordering::Greater => &boxed_node.right,
+                Ordering::Equal => return Some(&boxed_node.val),
+            }
+        }
+        None
+    }
+
+    // The values from the root down to `val` (both included); None if `val` is not in the tree;
+    pub fn path_to(&self, val: &T) -> Option<Vec<&T>> {
+        let mut path: Vec<&T> = Vec::new();
+        let mut link: &Link<T> = &self.root;
+        while let Some(boxed_node) = link {
+            path.push(&boxed_node.val);
+            link = match val.cmp(&boxed_node.val) {
+                Ordering::Less => &boxed_node.left,
+                Ordering::Greater => &boxed_node.right,
+                Ordering::Equal => return Some(path),
+            }
+        }
+        None
     }
 
-    pub fn preorder<F>(&self, f: F)
-    where
-        F: Fn(&T) + Copy,
-    {
-        preorder(&self.root, f);
+    // The number of edges from the root to `val`; the root is at depth 0;
+    pub fn depth_of(&self, val: &T) -> Option<usize> {
+        depth_below(self.root.as_deref(), val)
+    }
+
+    pub fn lowest_common_ancestor(&self, a: &T, b: &T) -> Option<&T> {
+        self.split_point(a, b).map(|(node, _, _)| &node.val)
+    }
+
+    // The number of edges on the path between `a` and `b`, which goes through their lowest common
+    // ancestor;
+    pub fn distance(&self, a: &T, b: &T) -> Option<usize> {
+        self.split_point(a, b).map(|(_, da, db)| da + db)
+    }
+
+    // Where the search paths of `a` and `b` split, that's their lowest common ancestor: while
+    // both are less than a node, both are in its left subtree, and so on; at the first node
+    // between them (or equal to one of them) they go different ways. Return it with the depths
+    // of `a` and `b` below it, if both are found. O(height).
+    fn split_point(&self, a: &T, b: &T) -> Option<(&Node<T>, usize, usize)> {
+        let (lo, hi) = if a <= b { (a, b) } else { (b, a) };
+        let mut link: &Link<T> = &self.root;
+        while let Some(boxed_node) = link {
+            if *hi < boxed_node.val {
+                link = &boxed_node.left;
+            } else if *lo > boxed_node.val {
+                link = &boxed_node.right;
+            } else {
+                let node: &Node<T> = boxed_node;
+                let da = depth_below(Some(node), a)?;
+                let db = depth_below(Some(node), b)?;
+                return Some((node, da, db));
+            }
+        }
+        None
     }
 
-    pub fn preorder_recursive<F>(&self, f: F)
-    where
-        F: Fn(&T) + Copy,
-    {
-        preorder_recursive(&self.root, f);
+    // Same values and same shape: walk both trees in lockstep with one stack of node pairs;
+    pub fn structurally_eq(&self, other: &Self) -> bool {
+        let mut stack: Vec<(&Link<T>, &Link<T>)> = vec![(&self.root, &other.root)];
+        while let Some(pair) = stack.pop() {
+            match pair {
+                (None, None) => {}
+                (Some(a), Some(b)) => {
+                    if a.val != b.val {
+                        return false;
+                    }
+                    stack.push((&a.left, &b.left));
+                    stack.push((&a.right, &b.right));
+                }
+                _ => return false,
+            }
+        }
+        true
     }
 
-    pub fn get_preorder_itr(&self) -> PreorderItr<T> {
-        PreorderItr::new(&self.root)
+    pub fn height(&self) -> usize {
+        self.height
     }
 
-    pub fn inorder<F>(&self, f: F)
-    where
-        F: Fn(&T) + Copy,
-    {
-        inorder(&self.root, f);
+    // a read-only handle to the root, for writing custom algorithms over the tree, see NodeRef;
+    pub fn root(&self) -> Option<NodeRef<'_, T>> {
+        NodeRef::from_link(&self.root)
     }
 
-    pub fn inorder_recursive<F>(&self, f: F)
-    where
-        F: Fn(&T) + Copy,
-    {
-        inorder_recursive(&self.root, f);
+    // one iterator for every traversal order, see Iter;
+    pub fn iter(&self, order: Order) -> Iter<'_, T> {
+        Iter::new(self.root.as_deref(), order, self.height)
     }
 
-    pub fn get_inorder_itr(&self) -> InorderItr<T> {
-        InorderItr::new(&self.root)
+    // depth first walk calling the hooks of `visitor` on every node, see Visitor;
+    pub fn walk<V: Visitor<T>>(&self, visitor: &mut V) {
+        walk(self.root.as_deref(), self.height, visitor);
     }
 }
 
-pub fn preorder_recursive<T, F>(link: &Link<T>, f: F)
-where
-    F: Fn(&T) + Copy,
-{
-    //`if let` is a shortcut for match, same as `while let` in BSTree::insert(); see comments there;
-    if let Some(boxed_node) = link {
-        f(&boxed_node.val);
-        preorder(&boxed_node.left, f);
-        preorder(&boxed_node.right, f);
+impl<T: Ord> Default for BSTree<T> {
+    fn default() -> Self {
+        BSTree::new()
     }
 }
 
-pub fn preorder<T, F>(link: &Link<T>, f: F)
-where
-    F: Fn(&T) + Copy,
-{
-    let mut stack: LinkedList<&Node<T>> = LinkedList::new();
+// The default drop of Box is recursive, which overflows the stack on a skewed (list-like) tree;
+// so take the nodes apart with an explicit stack instead.
+impl<T> Drop for BSTree<T> {
+    fn drop(&mut self) {
+        let mut stack: Vec<Box<Node<T>>> = Vec::new();
+        if let Some(boxed_node) = self.root.take() {
+            stack.push(boxed_node);
+        }
+        while let Some(mut boxed_node) = stack.pop() {
+            if let Some(left) = boxed_node.left.take() {
+                stack.push(left);
+            }
+            if let Some(right) = boxed_node.right.take() {
+                stack.push(right);
+            }
+            //`boxed_node` has no children now and is dropped here;
+        }
+    }
+}
+
+// Copy the exact shape, without recursion: in postorder both subtrees of a node come before it,
+// the left one first; so keep the copied subtrees on a stack, and a node takes its right and
+// then its left subtree from the top (if it has them).
+impl<T: Clone> Clone for BSTree<T> {
+    fn clone(&self) -> Self {
+        let mut built: Vec<Box<Node<T>>> = Vec::new();
+        let mut nodes = Iter::new(self.root.as_deref(), Order::Post, self.height);
+        while let Some(node) = nodes.next_node() {
+            let right = if node.right.is_some() {
+                built.pop()
+            } else {
+                None
+            };
+            let left = if node.left.is_some() {
+                built.pop()
+            } else {
+                None
+            };
+            built.push(Box::new(Node {
+                val: node.val.clone(),
+                left,
+                right,
+            }));
+        }
+        BSTree {
+            root: built.pop(),
+            height: self.height,
+        }
+    }
+}
 
-    // if link is not None, push it into stack;
-    if let Some(boxed_node) = link {
-        stack.push_back(boxed_node.deref());
+// Printed as a set in order, e.g. {1, 2, 3}; the shape is not shown (see NodeRef to render it).
+impl<T: fmt::Debug> fmt::Debug for BSTree<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set()
+            .entries(Iter::new(self.root.as_deref(), Order::In, self.height))
+            .finish()
     }
+}
 
-    // same as while(!stack.is_empty()) {...}
-    while let Some(node) = stack.pop_back() {
-        f(&node.val);
+// Equality, ordering and hashing are all by the in-order content, like a sorted Vec: two trees
+// holding the same values are equal whatever their shapes; see structurally_eq() for the shape.
+impl<T: PartialEq> PartialEq for BSTree<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Iter::new(self.root.as_deref(), Order::In, self.height).eq(Iter::new(
+            other.root.as_deref(),
+            Order::In,
+            other.height,
+        ))
+    }
+}
 
-        // why not `if let Some(boxed_node) = node.right`?
-        // because that's match value by value, causing node.right partial moved;
-        if let Some(boxed_node) = &node.right {
-            stack.push_back(boxed_node.deref());
-        }
-        if let Some(boxed_node) = &node.left {
-            stack.push_back(boxed_node.deref());
+impl<T: Eq> Eq for BSTree<T> {}
+
+impl<T: PartialOrd> PartialOrd for BSTree<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Iter::new(self.root.as_deref(), Order::In, self.height).partial_cmp(Iter::new(
+            other.root.as_deref(),
+            Order::In,
+            other.height,
+        ))
+    }
+}
+
+impl<T: Ord> Ord for BSTree<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        Iter::new(self.root.as_deref(), Order::In, self.height).cmp(Iter::new(
+            other.root.as_deref(),
+            Order::In,
+            other.height,
+        ))
+    }
+}
+
+// The count goes in too (after the values, as it's not known before), so that e.g. {1, 2} and
+// {1}, {2} don't feed the same sequence to the hasher when hashed one after another;
+impl<T: Hash> Hash for BSTree<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let mut n: usize = 0;
+        for val in Iter::new(self.root.as_deref(), Order::In, self.height) {
+            val.hash(state);
+            n += 1;
         }
+        state.write_usize(n);
     }
 }
 
-pub fn inorder_recursive<T, F>(link: &Link<T>, f: F)
+// Build a perfectly balanced tree from the first `n` nodes of `nodes`, which yields the nodes
+// in order (their children are overwritten). The middle node must be the root, but the nodes
+// come one by one; so build the left subtree from the first (n-1)/2 nodes, then the next node
+// is the root, and the rest make the right subtree. Every node is touched once, so it's O(n),
+// and the recursion is only O(log(n)) deep.
+pub(crate) fn build_balanced<T, I>(nodes: &mut I, n: usize) -> Link<T>
 where
-    F: Fn(&T) + Copy,
+    I: Iterator<Item = Box<Node<T>>>,
 {
-    if let Some(boxed_node) = link {
-        inorder_recursive(&boxed_node.left, f);
-        f(&boxed_node.val);
-        inorder_recursive(&boxed_node.right, f);
+    if n == 0 {
+        return None;
+    }
+    let left_n = (n - 1) / 2;
+    let left = build_balanced(nodes, left_n);
+    let mut root = nodes.next().expect("build_balanced: too few nodes");
+    root.left = left;
+    root.right = build_balanced(nodes, n - 1 - left_n);
+    Some(root)
+}
+
+// The number of edges from `node` down to `val`;
+fn depth_below<T: Ord>(mut node: Option<&Node<T>>, val: &T) -> Option<usize> {
+    let mut depth: usize = 0;
+    while let Some(n) = node {
+        node = match val.cmp(&n.val) {
+            Ordering::Less => n.left.as_deref(),
+            Ordering::Greater => n.right.as_deref(),
+            Ordering::Equal => return Some(depth),
+        };
+        depth += 1;
+    }
+    None
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RebuildError {
+    // the two sequences have different lengths;
+    LengthMismatch,
+    // the inorder sequence of a BST must be sorted;
+    NotSorted,
+    // the two sequences don't describe the same tree;
+    Mismatch,
+}
+
+impl fmt::Display for RebuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            RebuildError::LengthMismatch => "sequences of different lengths",
+            RebuildError::NotSorted => "inorder sequence not sorted",
+            RebuildError::Mismatch => "sequences of different trees",
+        };
+        write!(f, "{}", msg)
     }
 }
 
-pub fn inorder<T, F>(mut link: &Link<T>, f: F)
+impl Error for RebuildError {}
+
+fn check_sequences<T: Ord>(n: usize, inorder: &[T]) -> Result<(), RebuildError> {
+    if n != inorder.len() {
+        return Err(RebuildError::LengthMismatch);
+    }
+    if !inorder.windows(2).all(|w| w[0] <= w[1]) {
+        return Err(RebuildError::NotSorted);
+    }
+    Ok(())
+}
+
+// Build the subtree whose inorder sequence is `inorder`, taking its nodes from `vals`: the
+// first one is the root; find it in `inorder`, then whatever is on its left there makes the
+// left subtree, and whatever on its right makes the right subtree. `vals` yields the preorder
+// sequence (root, left, right), or the postorder sequence backwards (root, right, left) if
+// `right_first`.
+//
+// The inorder sequence of a BST is sorted, so the root is found by binary search. insert() puts
+// an equal value into the left subtree, so the root is the last one of its equals.
+//
+// Every node taken from `vals` is the root of its subtree, so the result has exactly the given
+// preorder (or postorder) sequence; and it's split by the given inorder sequence, so it has
+// exactly that inorder sequence too. If a root isn't found where it should be, the sequences
+// belong to different trees.
+//
+// No recursion, as a skewed tree is as deep as it's long: the subtrees still to build wait on a
+// stack, the one to build first on top. And the nodes aren't boxed until the end, but kept flat
+// in a Vec, their children by index, so that an error drops a Vec rather than a (maybe deep)
+// half-built tree.
+fn rebuild<T, I>(vals: &mut I, inorder: &[T], right_first: bool) -> Result<Link<T>, RebuildError>
 where
-    F: Fn(&T) + Copy,
+    T: Ord,
+    I: Iterator<Item = T>,
 {
-    let mut stack: LinkedList<&Node<T>> = LinkedList::new();
+    // (val, left, right);
+    let mut nodes: Vec<(T, Option<usize>, Option<usize>)> = Vec::with_capacity(inorder.len());
+    // (the range of the subtree in `inorder`, its parent and whether it's the left child);
+    let mut todo = vec![(0, inorder.len(), None::<(usize, bool)>)];
+    while let Some((lo, hi, parent)) = todo.pop() {
+        if lo == hi {
+            continue;
+        }
+        let val = vals.next().ok_or(RebuildError::LengthMismatch)?;
+        let k = lo + inorder[lo..hi].partition_point(|x| *x <= val);
+        if k == lo || inorder[k - 1] != val {
+            return Err(RebuildError::Mismatch);
+        }
 
-    loop {
-        while let Some(boxed_node) = link {
-            stack.push_back(boxed_node.deref());
-            link = &boxed_node.left;
+        let i = nodes.len();
+        nodes.push((val, None, None));
+        match parent {
+            Some((p, true)) => nodes[p].1 = Some(i),
+            Some((p, false)) => nodes[p].2 = Some(i),
+            None => {}
+        }
+        let (left, right) = ((lo, k - 1, Some((i, true))), (k, hi, Some((i, false))));
+        if right_first {
+            todo.push(left);
+            todo.push(right);
+        } else {
+            todo.push(right);
+            todo.push(left);
         }
+    }
+
+    // a node comes after its parent in `nodes`, so boxing them from the back boxes the children
+    // before their parent takes them;
+    let mut boxed: Vec<Link<T>> = (0..nodes.len()).map(|_| None).collect();
+    while let Some((val, left, right)) = nodes.pop() {
+        boxed[nodes.len()] = Some(Box::new(Node {
+            val,
+            left: left.and_then(|l| boxed[l].take()),
+            right: right.and_then(|r| boxed[r].take()),
+        }));
+    }
+    Ok(boxed.into_iter().next().flatten())
+}
+
+// A read-only handle to a node: the value and the 2 children, nothing else, so the outside can
+// walk the tree its own way without seeing Node or Link. It's just a shared ref, so it's Copy and
+// can't outlive the tree (nor let it be mutated while it's alive).
+pub struct NodeRef<'a, T> {
+    node: &'a Node<T>,
+}
+
+impl<'a, T> NodeRef<'a, T> {
+    fn from_link(link: &'a Link<T>) -> Option<Self> {
+        link.as_deref().map(|node| NodeRef { node })
+    }
+
+    pub fn value(&self) -> &'a T {
+        &self.node.val
+    }
+
+    pub fn left(&self) -> Option<NodeRef<'a, T>> {
+        NodeRef::from_link(&self.node.left)
+    }
+
+    pub fn right(&self) -> Option<NodeRef<'a, T>> {
+        NodeRef::from_link(&self.node.right)
+    }
+}
+
+// derive would ask for T: Clone, which is not needed to copy a ref;
+impl<'a, T> Clone for NodeRef<'a, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T> Copy for NodeRef<'a, T> {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Order {
+    Pre,
+    In,
+    Post,
+    Level,
+}
+
+// The hooks called by BSTree::walk(), each with the value of the node and its depth (the root
+// is at 0): `enter` before the left subtree (preorder position), `visit` between the subtrees
+// (inorder position) and `leave` after the right subtree (postorder position). All of them do
+// nothing by default, so a visitor only implements what it needs; e.g. computing the height is
+// just `enter` keeping the max depth.
+pub trait Visitor<T> {
+    fn enter(&mut self, _val: &T, _depth: usize) {}
+    fn visit(&mut self, _val: &T, _depth: usize) {}
+    fn leave(&mut self, _val: &T, _depth: usize) {}
+}
+
+// All that Iter and walk() need of a node: its value and its children. So a tree whose nodes
+// keep more than that (e.g. the treap's priority) gets the same traversals, with its own node
+// type; Node is the one of BSTree, and the default.
+pub trait BinaryNode {
+    type Val;
+    fn val(&self) -> &Self::Val;
+    fn left(&self) -> Option<&Self>;
+    fn right(&self) -> Option<&Self>;
+}
+
+impl<T> BinaryNode for Node<T> {
+    type Val = T;
+
+    fn val(&self) -> &T {
+        &self.val
+    }
+
+    fn left(&self) -> Option<&Self> {
+        self.left.as_deref()
+    }
+
+    fn right(&self) -> Option<&Self> {
+        self.right.as_deref()
+    }
+}
 
-        if stack.is_empty() {
-            break;
+// Entries of the traversal stacks that live inside the stack itself; 2 per level is enough for
+// a balanced tree of 2^32 nodes, so shallow trees never touch the heap.
+const INLINE: usize = 64;
+
+// The stack of the traversals. A LinkedList used to be here, which allocates on every push;
+// this one keeps the first INLINE entries in an array, and only the deeper ones in a Vec, which
+// is reserved once from the tree height. The capacity is only a hint: a stack that outgrows it
+// just reallocates.
+struct Stack<E> {
+    inline: [Option<E>; INLINE],
+    len: usize,
+    spill: Vec<E>,
+}
+
+impl<E: Copy> Stack<E> {
+    // none of the traversals keeps more than 2 entries per level (a node and a child of it);
+    fn for_height(height: usize) -> Self {
+        Stack {
+            inline: [None; INLINE],
+            len: 0,
+            spill: Vec::with_capacity((2 * height + 1).saturating_sub(INLINE)),
         }
+    }
 
-        let node = stack.pop_back().unwrap();
-        f(&node.val);
+    fn push(&mut self, e: E) {
+        if self.len < INLINE {
+            self.inline[self.len] = Some(e);
+        } else {
+            self.spill.push(e);
+        }
+        self.len += 1;
+    }
 
-        link = &node.right;
+    fn pop(&mut self) -> Option<E> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        if self.len < INLINE {
+            self.inline[self.len].take()
+        } else {
+            self.spill.pop()
+        }
     }
 }
 
-pub struct PreorderItr<'a, T> {
-    stack: LinkedList<&'a Node<T>>,
+// Where the walk is at for a node on the stack: not started yet, left subtree done, right subtree
+// done;
+#[derive(Clone, Copy)]
+enum Step {
+    Enter,
+    Visit,
+    Leave,
 }
 
-impl<'a, T> Iterator for PreorderItr<'a, T> {
-    type Item = &'a T;
+// The walker: one explicit stack of (node, depth, step) instead of recursion, so a degenerate
+// (list-like) tree cannot overflow the call stack; every node goes through the 3 steps in turn.
+// `height` sizes the stack, 0 if unknown.
+pub(crate) fn walk<N, V>(root: Option<&N>, height: usize, visitor: &mut V)
+where
+    N: BinaryNode,
+    V: Visitor<N::Val>,
+{
+    let mut stack: Stack<(&N, usize, Step)> = Stack::for_height(height);
+    if let Some(node) = root {
+        stack.push((node, 0, Step::Enter));
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        match self.stack.pop_back() {
-            None => None,
-            Some(node) => {
-                if let Some(boxed_node) = &node.right {
-                    self.stack.push_back(boxed_node.deref());
+    while let Some((node, depth, step)) = stack.pop() {
+        match step {
+            Step::Enter => {
+                visitor.enter(node.val(), depth);
+                stack.push((node, depth, Step::Visit));
+                if let Some(left) = node.left() {
+                    stack.push((left, depth + 1, Step::Enter));
                 }
-                if let Some(boxed_node) = &node.left {
-                    self.stack.push_back(boxed_node.deref());
+            }
+            Step::Visit => {
+                visitor.visit(node.val(), depth);
+                stack.push((node, depth, Step::Leave));
+                if let Some(right) = node.right() {
+                    stack.push((right, depth + 1, Step::Enter));
                 }
-                Some(&node.val)
             }
+            Step::Leave => visitor.leave(node.val(), depth),
         }
     }
 }
 
-impl<'a, T> PreorderItr<'a, T> {
-    pub fn new(link: &'a Link<T>) -> Self {
-        let mut stack: LinkedList<&'a Node<T>> = LinkedList::new();
-        if let Some(boxed_node) = link {
-            stack.push_back(boxed_node.deref());
+// the number of levels, by the walker; for trees built without going through insert();
+fn height<T>(link: &Link<T>) -> usize {
+    struct Height(usize);
+    impl<T> Visitor<T> for Height {
+        fn enter(&mut self, _val: &T, depth: usize) {
+            self.0 = self.0.max(depth + 1);
         }
-        PreorderItr { stack }
     }
+    let mut h = Height(0);
+    walk(link.as_deref(), 0, &mut h);
+    h.0
 }
 
-pub struct InorderItr<'a, T> {
-    stack: LinkedList<&'a Node<T>>,
+// A lazy iterator for all the 4 orders: the depth first ones use a stack, and level order a
+// queue.
+//
+// For Pre, a node is yielded as soon as it's popped, after pushing its right and then its left
+// child (so the left one comes out first). For In and Post, a node is first "expanded": pushed
+// back with the flag set, together with its children, in the reverse of the order they must come
+// out; it's yielded when popped again with the flag set.
+//
+// The nodes are BSTree's by default, or those of any tree that implements BinaryNode for them.
+pub struct Iter<'a, T, N = Node<T>> {
+    order: Order,
+    stack: Stack<(&'a N, bool)>,
+    queue: VecDeque<&'a N>,
+    _val: PhantomData<&'a T>,
 }
 
-impl<'a, T> Iterator for InorderItr<'a, T> {
-    type Item = &'a T;
+impl<'a, T, N: BinaryNode<Val = T>> Iter<'a, T, N> {
+    // `height` sizes the stack, 0 if unknown;
+    pub(crate) fn new(root: Option<&'a N>, order: Order, height: usize) -> Self {
+        let mut itr = Iter {
+            order,
+            stack: Stack::for_height(if order == Order::Level { 0 } else { height }),
+            queue: VecDeque::new(),
+            _val: PhantomData,
+        };
+        if order == Order::Level {
+            itr.enqueue(root);
+        } else {
+            itr.push(root);
+        }
+        itr
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        match self.stack.pop_back() {
-            None => None,
-            Some(node) => {
-                self.push_till_next(&node.right);
-                Some(&node.val)
-            }
+    fn push(&mut self, node: Option<&'a N>) {
+        if let Some(node) = node {
+            self.stack.push((node, false));
         }
     }
-}
 
-impl<'a, T> InorderItr<'a, T> {
-    pub fn new(link: &'a Link<T>) -> Self {
-        let mut itr = InorderItr {
-            stack: LinkedList::new(),
-        };
-        itr.push_till_next(link);
-        itr
+    fn enqueue(&mut self, node: Option<&'a N>) {
+        if let Some(node) = node {
+            self.queue.push_back(node);
+        }
     }
 
-    // `link` is a shared (immutable) ref to Link<T> instance (the instance cannot be mutated); but
-    // `link` can ref different Link<T> instances, hence the `mut` before it;
-    fn push_till_next(&mut self, mut link: &'a Link<T>) {
-        while let Some(boxed_node) = link {
-            self.stack.push_back(boxed_node.deref());
-            link = &boxed_node.left;
+    // the nodes themselves are needed by BSTree::clone();
+    fn next_node(&mut self) -> Option<&'a N> {
+        if self.order == Order::Level {
+            let node = self.queue.pop_front()?;
+            self.enqueue(node.left());
+            self.enqueue(node.right());
+            return Some(node);
         }
+
+        while let Some((node, expanded)) = self.stack.pop() {
+            if expanded {
+                return Some(node);
+            }
+            match self.order {
+                Order::Pre => {
+                    self.push(node.right());
+                    self.push(node.left());
+                    return Some(node);
+                }
+                Order::In => {
+                    self.push(node.right());
+                    self.stack.push((node, true));
+                    self.push(node.left());
+                }
+                Order::Post => {
+                    self.stack.push((node, true));
+                    self.push(node.right());
+                    self.push(node.left());
+                }
+                Order::Level => unreachable!(),
+            }
+        }
+        None
+    }
+}
+
+impl<'a, T, N: BinaryNode<Val = T>> Iterator for Iter<'a, T, N> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_node().map(|node| node.val())
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::BSTree;
+    use super::{BSTree, Node, NodeRef, Order, RebuildError, Stack, Visitor, INLINE};
+    use std::collections::HashSet;
 
     #[test]
     pub fn test_preorder() {
@@ -239,26 +736,7 @@ mod test {
             bst.insert(*val);
         }
 
-        let mut _i: usize = 0;
-        let _preorder_list = vec![8, 4, 6, 5, 7, 10, 9, 12, 13];
-
-        println!("preorder_recursive");
-        bst.preorder_recursive(|v: &i32| {
-            print!("{} ", v);
-            //assert_eq!(*v, preorder_list[i]);
-            //i = i+1;
-        });
-        println!();
-
-        println!("preorder");
-        bst.preorder(|v: &i32| {
-            print!("{} ", v);
-            //assert_eq!(*v, preorder_list[i]);
-            //i = i+1;
-        });
-        println!();
-
-        let mut pre_itr = bst.get_preorder_itr();
+        let mut pre_itr = bst.iter(Order::Pre);
 
         //this would not compile, because we have an immutable reference
         //in `pre_itr`, so mutation is not allowed;
@@ -266,13 +744,12 @@ mod test {
         //bst.insert(100);
 
         println!("preorder_itr");
-        while let Some(v) = pre_itr.next() {
+        for v in &mut pre_itr {
             print!("{} ", v);
         }
         println!();
 
-        let pre_itr2 = bst.get_preorder_itr();
-        let c: Vec<&i32> = pre_itr2.collect();
+        let c: Vec<&i32> = bst.iter(Order::Pre).collect();
         println!("{:?}", c);
         let expect: Vec<&i32> = vec![&8, &4, &6, &5, &7, &10, &9, &12, &13];
         assert_eq!(c, expect);
@@ -286,27 +763,13 @@ mod test {
             bst.insert(String::from(*val));
         }
 
-        println!("inorder");
-        bst.inorder(|v| {
-            print!("{} ", v);
-        });
-        println!();
-
-        println!("inorder_recursive");
-        bst.inorder_recursive(|v| {
-            print!("{} ", v);
-        });
-        println!();
-
         println!("inorder_itr");
-        let mut in_itr = bst.get_inorder_itr();
-        while let Some(v) = in_itr.next() {
+        for v in bst.iter(Order::In) {
             print!("{}", v);
         }
         println!();
 
-        let in_itr2 = bst.get_inorder_itr();
-        let c: Vec<&String> = in_itr2.collect();
+        let c: Vec<&String> = bst.iter(Order::In).collect();
         println!("{:?}", c);
 
         let ra = &String::from("a");
@@ -321,4 +784,366 @@ mod test {
         let expect = vec![ra, rb, rc, rd, re, rf, rg, rh, ri];
         assert_eq!(c, expect);
     }
+
+    #[test]
+    pub fn test_post_level_order() {
+        //        8
+        //      /   \
+        //     4     10
+        //      \   /  \
+        //      6  9   12
+        //     / \       \
+        //    5   7       13
+        let mut bst: BSTree<i32> = BSTree::new();
+        for val in [8, 4, 10, 6, 5, 7, 9, 12, 13].iter() {
+            bst.insert(*val);
+        }
+        let c: Vec<i32> = bst.iter(Order::Post).cloned().collect();
+        assert_eq!(c, vec![5, 7, 6, 4, 9, 13, 12, 10, 8]);
+        let c: Vec<i32> = bst.iter(Order::Level).cloned().collect();
+        assert_eq!(c, vec![8, 4, 10, 6, 9, 12, 5, 7, 13]);
+
+        let empty: BSTree<i32> = BSTree::new();
+        for order in [Order::Pre, Order::In, Order::Post, Order::Level].iter() {
+            assert_eq!(empty.iter(*order).next(), None);
+        }
+    }
+
+    // records the 3 hooks in the order they are called, and the max depth seen;
+    struct Recorder {
+        events: Vec<(char, i32, usize)>,
+        height: usize,
+    }
+
+    impl Visitor<i32> for Recorder {
+        fn enter(&mut self, val: &i32, depth: usize) {
+            self.events.push(('e', *val, depth));
+            self.height = self.height.max(depth + 1);
+        }
+        fn visit(&mut self, val: &i32, depth: usize) {
+            self.events.push(('v', *val, depth));
+        }
+        fn leave(&mut self, val: &i32, depth: usize) {
+            self.events.push(('l', *val, depth));
+        }
+    }
+
+    #[test]
+    pub fn test_visitor() {
+        let mut bst: BSTree<i32> = BSTree::new();
+        for val in [8, 4, 10, 6, 5, 7, 9, 12, 13].iter() {
+            bst.insert(*val);
+        }
+        let mut rec = Recorder {
+            events: Vec::new(),
+            height: 0,
+        };
+        bst.walk(&mut rec);
+        assert_eq!(rec.height, 4);
+        assert_eq!(
+            &rec.events[..4],
+            &[('e', 8, 0), ('e', 4, 1), ('v', 4, 1), ('e', 6, 2)]
+        );
+
+        // each hook alone gives one of the depth first orders;
+        for (hook, order) in [('e', Order::Pre), ('v', Order::In), ('l', Order::Post)].iter() {
+            let c: Vec<i32> = rec
+                .events
+                .iter()
+                .filter(|e| e.0 == *hook)
+                .map(|e| e.1)
+                .collect();
+            let expect: Vec<i32> = bst.iter(*order).cloned().collect();
+            assert_eq!(c, expect);
+        }
+    }
+
+    // 2 algorithms written from the outside with NodeRef only: the diameter (the number of
+    // nodes on the longest path between 2 leaves), and a serialization in preorder with "#" for
+    // the missing children, which tells the shape exactly;
+    fn height_and_diameter(node: Option<NodeRef<i32>>) -> (usize, usize) {
+        match node {
+            None => (0, 0),
+            Some(n) => {
+                let (lh, ld) = height_and_diameter(n.left());
+                let (rh, rd) = height_and_diameter(n.right());
+                (1 + lh.max(rh), (lh + rh + 1).max(ld).max(rd))
+            }
+        }
+    }
+
+    fn serialize(node: Option<NodeRef<i32>>, out: &mut Vec<String>) {
+        match node {
+            None => out.push(String::from("#")),
+            Some(n) => {
+                out.push(n.value().to_string());
+                serialize(n.left(), out);
+                serialize(n.right(), out);
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_node_ref() {
+        //        8
+        //      /   \
+        //     4     10
+        //      \   /  \
+        //      6  9   12
+        //     / \       \
+        //    5   7       13
+        let mut bst: BSTree<i32> = BSTree::new();
+        for val in [8, 4, 10, 6, 5, 7, 9, 12, 13].iter() {
+            bst.insert(*val);
+        }
+        let root = bst.root().unwrap();
+        assert_eq!(*root.value(), 8);
+        assert_eq!(root.left().unwrap().left().map(|n| *n.value()), None);
+        assert_eq!(*root.right().unwrap().left().unwrap().value(), 9);
+
+        // 5-6-4-8-10-12-13;
+        assert_eq!(height_and_diameter(bst.root()), (4, 7));
+
+        let mut out = Vec::new();
+        serialize(bst.root(), &mut out);
+        assert_eq!(out.join(" "), "8 4 # 6 5 # # 7 # # 10 9 # # 12 # 13 # #");
+
+        let empty: BSTree<i32> = BSTree::new();
+        assert!(empty.root().is_none());
+        assert_eq!(height_and_diameter(empty.root()), (0, 0));
+    }
+
+    #[test]
+    pub fn test_traits() {
+        let mut a: BSTree<i32> = BSTree::default();
+        for val in [8, 4, 10, 6, 5, 7, 9, 12, 13].iter() {
+            a.insert(*val);
+        }
+        let b = a.clone();
+        assert_eq!(a, b);
+        assert!(a.structurally_eq(&b));
+        assert_eq!(format!("{:?}", b), "{4, 5, 6, 7, 8, 9, 10, 12, 13}");
+        assert_eq!(format!("{:?}", BSTree::<i32>::new()), "{}");
+
+        // same values, another shape;
+        let c = BSTree::from_sorted(vec![4, 5, 6, 7, 8, 9, 10, 12, 13]);
+        assert_eq!(a, c);
+        assert!(!a.structurally_eq(&c));
+        let d = BSTree::from_sorted(vec![4, 5, 6]);
+        assert_ne!(a, d);
+        assert!(!d.structurally_eq(&BSTree::from_sorted(vec![4, 5, 7])));
+
+        // ordered like sorted Vecs;
+        assert!(d < a);
+        assert!(BSTree::from_sorted(vec![1, 100]) < BSTree::from_sorted(vec![2]));
+        assert!(BSTree::new() < BSTree::from_sorted(vec![0]));
+        let mut trees = vec![a.clone(), d.clone(), BSTree::new()];
+        trees.sort();
+        assert_eq!(trees, vec![BSTree::new(), d.clone(), a.clone()]);
+
+        let mut set = HashSet::new();
+        set.insert(a);
+        assert!(set.contains(&c));
+        assert!(!set.contains(&d));
+    }
+
+    #[test]
+    pub fn test_traits_skewed() {
+        // a list-like tree far deeper than a recursive clone/debug/drop could go;
+        let n = 200_000;
+        let mut root = None;
+        for val in (0..n).rev() {
+            root = Some(Box::new(Node {
+                val,
+                left: None,
+                right: root,
+            }));
+        }
+        let skewed = BSTree { root, height: n };
+        let copy = skewed.clone();
+        assert!(skewed.structurally_eq(&copy));
+        assert_eq!(copy, BSTree::from_sorted((0..n).collect()));
+        let s = format!("{:?}", copy);
+        assert!(s.starts_with("{0, 1, 2,") && s.ends_with("199999}"));
+    }
+
+    #[test]
+    pub fn test_height() {
+        let mut bst: BSTree<i32> = BSTree::new();
+        assert_eq!(bst.height(), 0);
+        for val in [8, 4, 10, 6, 5, 7, 9, 12, 13].iter() {
+            bst.insert(*val);
+        }
+        assert_eq!(bst.height(), 4);
+        for n in 0..=9 {
+            let bst: BSTree<i32> = BSTree::from_sorted((0..n).collect());
+            let mut levels = 0;
+            while 1 << levels <= n {
+                levels += 1;
+            }
+            assert_eq!(bst.height(), levels as usize);
+        }
+        let bst = BSTree::from_preorder_inorder(vec![3, 1, 2], &[1, 2, 3]).unwrap();
+        assert_eq!(bst.height(), 3);
+    }
+
+    // across the end of the inline storage and back, with a wrong (too small) capacity hint;
+    #[test]
+    pub fn test_stack() {
+        let mut stack: Stack<usize> = Stack::for_height(1);
+        for i in 0..3 * INLINE {
+            stack.push(i);
+        }
+        for i in (INLINE..3 * INLINE).rev() {
+            assert_eq!(stack.pop(), Some(i));
+        }
+        stack.push(1000);
+        assert_eq!(stack.pop(), Some(1000));
+        for i in (0..INLINE).rev() {
+            assert_eq!(stack.pop(), Some(i));
+        }
+        assert_eq!(stack.pop(), None);
+    }
+
+    #[test]
+    pub fn test_from_sorted() {
+        let bst: BSTree<i32> = BSTree::from_sorted((1..=7).collect());
+        let c: Vec<&i32> = bst.iter(Order::Pre).collect();
+        let expect: Vec<&i32> = vec![&4, &2, &1, &3, &6, &5, &7];
+        assert_eq!(c, expect);
+
+        let bst: BSTree<i32> = BSTree::from_sorted((1..=6).collect());
+        let c: Vec<&i32> = bst.iter(Order::In).collect();
+        let expect: Vec<&i32> = vec![&1, &2, &3, &4, &5, &6];
+        assert_eq!(c, expect);
+        assert_eq!(bst.get(&6), Some(&6));
+        assert_eq!(bst.get(&7), None);
+
+        let bst: BSTree<i32> = BSTree::from_sorted(Vec::new());
+        assert_eq!(bst.iter(Order::In).next(), None);
+    }
+
+    #[test]
+    pub fn test_ancestry() {
+        //        8
+        //      /   \
+        //     4     10
+        //      \   /  \
+        //      6  9   12
+        //     / \       \
+        //    5   7       13
+        let mut bst: BSTree<i32> = BSTree::new();
+        for val in [8, 4, 10, 6, 5, 7, 9, 12, 13].iter() {
+            bst.insert(*val);
+        }
+
+        assert_eq!(bst.path_to(&7), Some(vec![&8, &4, &6, &7]));
+        assert_eq!(bst.path_to(&8), Some(vec![&8]));
+        assert_eq!(bst.path_to(&11), None);
+
+        assert_eq!(bst.depth_of(&8), Some(0));
+        assert_eq!(bst.depth_of(&13), Some(3));
+        assert_eq!(bst.depth_of(&3), None);
+
+        assert_eq!(bst.lowest_common_ancestor(&5, &7), Some(&6));
+        assert_eq!(bst.lowest_common_ancestor(&7, &5), Some(&6));
+        assert_eq!(bst.lowest_common_ancestor(&5, &13), Some(&8));
+        assert_eq!(bst.lowest_common_ancestor(&4, &7), Some(&4));
+        assert_eq!(bst.lowest_common_ancestor(&9, &9), Some(&9));
+        assert_eq!(bst.lowest_common_ancestor(&9, &11), None);
+
+        assert_eq!(bst.distance(&5, &13), Some(6));
+        assert_eq!(bst.distance(&4, &7), Some(2));
+        assert_eq!(bst.distance(&9, &9), Some(0));
+        assert_eq!(bst.distance(&1, &9), None);
+
+        let empty: BSTree<i32> = BSTree::new();
+        assert_eq!(empty.lowest_common_ancestor(&1, &1), None);
+    }
+
+    #[test]
+    pub fn test_rebuild() {
+        let mut bst: BSTree<i32> = BSTree::new();
+        let insert_order = vec![8, 4, 10, 6, 5, 7, 9, 12, 13];
+        for val in insert_order.iter() {
+            bst.insert(*val);
+        }
+        let preorder: Vec<i32> = bst.iter(Order::Pre).cloned().collect();
+        let inorder: Vec<i32> = bst.iter(Order::In).cloned().collect();
+        let postorder = vec![5, 7, 6, 4, 9, 13, 12, 10, 8];
+
+        let bst1 = BSTree::from_preorder_inorder(preorder.clone(), &inorder).unwrap();
+        let c: Vec<i32> = bst1.iter(Order::Pre).cloned().collect();
+        assert_eq!(c, preorder);
+        let c: Vec<i32> = bst1.iter(Order::In).cloned().collect();
+        assert_eq!(c, inorder);
+
+        let bst2 = BSTree::from_postorder_inorder(postorder, &inorder).unwrap();
+        let c: Vec<i32> = bst2.iter(Order::Pre).cloned().collect();
+        assert_eq!(c, preorder);
+
+        //duplicates: 5(3(-, 5(5, -)), 7);
+        let mut bst: BSTree<i32> = BSTree::new();
+        for val in [5, 3, 5, 5, 7].iter() {
+            bst.insert(*val);
+        }
+        let preorder: Vec<i32> = bst.iter(Order::Pre).cloned().collect();
+        let inorder: Vec<i32> = bst.iter(Order::In).cloned().collect();
+        let bst3 = BSTree::from_preorder_inorder(preorder.clone(), &inorder).unwrap();
+        let c: Vec<i32> = bst3.iter(Order::Pre).cloned().collect();
+        assert_eq!(c, preorder);
+        let bst4 = BSTree::from_postorder_inorder(vec![5, 5, 3, 7, 5], &inorder).unwrap();
+        let c: Vec<i32> = bst4.iter(Order::Pre).cloned().collect();
+        assert_eq!(c, preorder);
+
+        let empty = BSTree::<i32>::from_preorder_inorder(vec![], &[]).unwrap();
+        assert_eq!(empty.iter(Order::Pre).next(), None);
+    }
+
+    #[test]
+    pub fn test_rebuild_error() {
+        let r = BSTree::from_preorder_inorder(vec![2, 1], &[1, 2, 3]);
+        assert_eq!(r.err(), Some(RebuildError::LengthMismatch));
+
+        let r = BSTree::from_preorder_inorder(vec![2, 1, 3], &[1, 3, 2]);
+        assert_eq!(r.err(), Some(RebuildError::NotSorted));
+
+        //4 is not in the inorder sequence;
+        let r = BSTree::from_preorder_inorder(vec![2, 1, 4], &[1, 2, 3]);
+        assert_eq!(r.err(), Some(RebuildError::Mismatch));
+
+        //2 is the root, so the next one, 3, must be the root of the left subtree [1];
+        let r = BSTree::from_preorder_inorder(vec![2, 3, 1], &[1, 2, 3]);
+        assert_eq!(r.err(), Some(RebuildError::Mismatch));
+
+        //from_sorted() puts the second 5 right of the first, which rebuild() can't tell;
+        let bst: BSTree<i32> = BSTree::from_sorted(vec![1, 5, 5]);
+        let preorder: Vec<i32> = bst.iter(Order::Pre).cloned().collect();
+        let inorder: Vec<i32> = bst.iter(Order::In).cloned().collect();
+        let copy = BSTree::from_preorder_inorder(preorder, &inorder).unwrap();
+        assert_eq!(copy, bst);
+        assert!(!copy.structurally_eq(&bst));
+    }
+
+    #[test]
+    pub fn test_rebuild_skewed() {
+        // a list-like tree far deeper than a recursive rebuild could go, both ways, and an error
+        // found at the bottom of it;
+        let n = if cfg!(miri) { 1000 } else { 200_000 };
+        let inorder: Vec<i32> = (0..n).collect();
+        let bst = BSTree::from_preorder_inorder(inorder.clone(), &inorder).unwrap();
+        assert_eq!(bst.height(), n as usize);
+        assert_eq!(bst.iter(Order::Pre).cloned().collect::<Vec<i32>>(), inorder);
+        let bst = BSTree::from_postorder_inorder(inorder.clone(), &inorder).unwrap();
+        assert_eq!(bst.height(), n as usize);
+        assert_eq!(
+            bst.iter(Order::Post).cloned().collect::<Vec<i32>>(),
+            inorder
+        );
+
+        let mut preorder = inorder.clone();
+        *preorder.last_mut().unwrap() = n;
+        let r = BSTree::from_preorder_inorder(preorder, &inorder);
+        assert_eq!(r.err(), Some(RebuildError::Mismatch));
+    }
 }
@@ -0,0 +1,498 @@
+use std::cmp::Ordering;
+use std::mem;
+use std::ops::{Bound, RangeBounds};
+
+// A B-tree with minimum degree `t` (see CLRS chapter 18):
+//     1. every node but the root holds t-1 ..= 2t-1 keys; the root holds 0 ..= 2t-1 keys;
+//     2. an internal node with n keys has n+1 children, and keys[i] sits between children[i]
+//        and children[i+1];
+//     3. all the leaves are at the same depth.
+// Many keys share one node (one allocation, adjacent in memory), so the tree is much shallower
+// than a binary one, and every node visited brings a whole cache line (or disk block) of keys.
+struct Node<K, V> {
+    keys: Vec<K>,
+    vals: Vec<V>,
+    //empty for a leaf;
+    children: Vec<Node<K, V>>,
+}
+
+pub struct BTree<K, V> {
+    root: Node<K, V>,
+    t: usize,
+    len: usize,
+}
+
+const DEFAULT_MIN_DEGREE: usize = 6;
+
+impl<K, V> Node<K, V> {
+    fn new(t: usize) -> Self {
+        Node {
+            keys: Vec::with_capacity(2 * t - 1),
+            vals: Vec::with_capacity(2 * t - 1),
+            children: Vec::new(),
+        }
+    }
+
+    fn is_leaf(&self) -> bool {
+        self.children.is_empty()
+    }
+}
+
+impl<K: Ord, V> Node<K, V> {
+    // children[i] is full (2t-1 keys); split it at its median key: the lower t-1 keys stay, the
+    // upper t-1 keys move to a new node which becomes children[i+1], and the median moves up to
+    // keys[i] of self;
+    fn split_child(&mut self, i: usize, t: usize) {
+        let child = &mut self.children[i];
+        let mut right = Node::new(t);
+        right.keys = child.keys.split_off(t);
+        right.vals = child.vals.split_off(t);
+        if !child.is_leaf() {
+            right.children = child.children.split_off(t);
+        }
+        let mid_key = child.keys.pop().unwrap();
+        let mid_val = child.vals.pop().unwrap();
+
+        self.keys.insert(i, mid_key);
+        self.vals.insert(i, mid_val);
+        self.children.insert(i + 1, right);
+    }
+
+    // merge children[i+1] and keys[i] into children[i]; both children have t-1 keys, so the
+    // merged one has 2t-1;
+    fn merge_children(&mut self, i: usize) {
+        let right = self.children.remove(i + 1);
+        let key = self.keys.remove(i);
+        let val = self.vals.remove(i);
+
+        let left = &mut self.children[i];
+        left.keys.push(key);
+        left.vals.push(val);
+        let Node {
+            keys,
+            vals,
+            children,
+        } = right;
+        left.keys.extend(keys);
+        left.vals.extend(vals);
+        left.children.extend(children);
+    }
+
+    // Before descending into children[i], make sure it has at least t keys, so that a key can be
+    // removed from it without going below t-1: borrow a key from a sibling through self, or
+    // merge it with a sibling. Returns the index of the child to descend into, which changes
+    // when merged with the left sibling.
+    fn fix_child(&mut self, i: usize, t: usize) -> usize {
+        if self.children[i].keys.len() >= t {
+            return i;
+        }
+
+        if i > 0 && self.children[i - 1].keys.len() >= t {
+            //borrow from the left sibling: its last key goes up, keys[i-1] goes down;
+            let (lefts, rights) = self.children.split_at_mut(i);
+            let left = &mut lefts[i - 1];
+            let child = &mut rights[0];
+            let key = mem::replace(&mut self.keys[i - 1], left.keys.pop().unwrap());
+            let val = mem::replace(&mut self.vals[i - 1], left.vals.pop().unwrap());
+            child.keys.insert(0, key);
+            child.vals.insert(0, val);
+            if let Some(grandchild) = left.children.pop() {
+                child.children.insert(0, grandchild);
+            }
+            i
+        } else if i + 1 < self.children.len() && self.children[i + 1].keys.len() >= t {
+            //borrow from the right sibling: its first key goes up, keys[i] goes down;
+            let (lefts, rights) = self.children.split_at_mut(i + 1);
+            let child = &mut lefts[i];
+            let right = &mut rights[0];
+            let key = mem::replace(&mut self.keys[i], right.keys.remove(0));
+            let val = mem::replace(&mut self.vals[i], right.vals.remove(0));
+            child.keys.push(key);
+            child.vals.push(val);
+            if !right.is_leaf() {
+                child.children.push(right.children.remove(0));
+            }
+            i
+        } else if i + 1 < self.children.len() {
+            self.merge_children(i);
+            i
+        } else {
+            self.merge_children(i - 1);
+            i - 1
+        }
+    }
+
+    // Remove `key` from the subtree; self has at least t keys, unless it's the root;
+    fn remove(&mut self, key: &K, t: usize) -> Option<(K, V)> {
+        match self.keys.binary_search(key) {
+            Ok(i) if self.is_leaf() => Some((self.keys.remove(i), self.vals.remove(i))),
+            Ok(i) => {
+                //replace the key with its predecessor or successor, which is in a leaf; if
+                //neither child can spare a key, merge them and go on in the merged one;
+                if self.children[i].keys.len() >= t {
+                    let (k, v) = self.children[i].remove_max(t);
+                    let k = mem::replace(&mut self.keys[i], k);
+                    let v = mem::replace(&mut self.vals[i], v);
+                    Some((k, v))
+                } else if self.children[i + 1].keys.len() >= t {
+                    let (k, v) = self.children[i + 1].remove_min(t);
+                    let k = mem::replace(&mut self.keys[i], k);
+                    let v = mem::replace(&mut self.vals[i], v);
+                    Some((k, v))
+                } else {
+                    self.merge_children(i);
+                    self.children[i].remove(key, t)
+                }
+            }
+            Err(_) if self.is_leaf() => None,
+            Err(i) => {
+                let i = self.fix_child(i, t);
+                self.children[i].remove(key, t)
+            }
+        }
+    }
+
+    fn remove_max(&mut self, t: usize) -> (K, V) {
+        if self.is_leaf() {
+            return (self.keys.pop().unwrap(), self.vals.pop().unwrap());
+        }
+        let i = self.fix_child(self.children.len() - 1, t);
+        self.children[i].remove_max(t)
+    }
+
+    fn remove_min(&mut self, t: usize) -> (K, V) {
+        if self.is_leaf() {
+            return (self.keys.remove(0), self.vals.remove(0));
+        }
+        let i = self.fix_child(0, t);
+        self.children[i].remove_min(t)
+    }
+}
+
+impl<K: Ord, V> BTree<K, V> {
+    pub fn new() -> Self {
+        Self::with_min_degree(DEFAULT_MIN_DEGREE)
+    }
+
+    // Every node holds t-1 ..= 2t-1 keys; t must be at least 2;
+    pub fn with_min_degree(t: usize) -> Self {
+        assert!(t >= 2, "BTree: min degree must be at least 2");
+        BTree {
+            root: Node::new(t),
+            t,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        let mut node: &Node<K, V> = &self.root;
+        loop {
+            match node.keys.binary_search(key) {
+                Ok(i) => return Some(&node.vals[i]),
+                Err(_) if node.is_leaf() => return None,
+                Err(i) => node = &node.children[i],
+            }
+        }
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let mut node: &mut Node<K, V> = &mut self.root;
+        loop {
+            match node.keys.binary_search(key) {
+                Ok(i) => return Some(&mut node.vals[i]),
+                Err(_) if node.is_leaf() => return None,
+                Err(i) => node = &mut node.children[i],
+            }
+        }
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    // Insert a key-value pair; if the key exists, its value is replaced and the old one is
+    // returned, the same as std::collections::BTreeMap.
+    //
+    // Single pass down (CLRS B-TREE-INSERT): a full node is split before we step into it, so
+    // its parent always has room for the median key moving up, and we never have to go back.
+    pub fn insert(&mut self, key: K, val: V) -> Option<V> {
+        let t = self.t;
+
+        //a full root is split by hanging it under a new empty root; this is the only way the
+        //tree grows taller;
+        if self.root.keys.len() == 2 * t - 1 {
+            let old_root = mem::replace(&mut self.root, Node::new(t));
+            self.root.children.push(old_root);
+            self.root.split_child(0, t);
+        }
+
+        let mut node: &mut Node<K, V> = &mut self.root;
+        loop {
+            let mut i = match node.keys.binary_search(&key) {
+                Ok(i) => return Some(mem::replace(&mut node.vals[i], val)),
+                Err(i) => i,
+            };
+
+            if node.is_leaf() {
+                node.keys.insert(i, key);
+                node.vals.insert(i, val);
+                self.len += 1;
+                return None;
+            }
+
+            if node.children[i].keys.len() == 2 * t - 1 {
+                node.split_child(i, t);
+                //the median moved up to keys[i]; it may be the key itself, or the key may go
+                //to the new right half;
+                match key.cmp(&node.keys[i]) {
+                    Ordering::Equal => return Some(mem::replace(&mut node.vals[i], val)),
+                    Ordering::Greater => i += 1,
+                    Ordering::Less => {}
+                }
+            }
+            node = &mut node.children[i];
+        }
+    }
+
+    // Remove a key, return its key-value pair.
+    //
+    // Single pass down (CLRS B-TREE-DELETE): before we step into a child, make sure it has at
+    // least t keys, by borrowing from a sibling or merging with one; so the key can be removed
+    // from a leaf, or replaced by its predecessor/successor, without any fix-up on the way back.
+    pub fn remove(&mut self, key: &K) -> Option<(K, V)> {
+        let removed = self.root.remove(key, self.t);
+        if removed.is_some() {
+            self.len -= 1;
+        }
+
+        //the root lost its last key in a merge: its only child becomes the root; this is the
+        //only way the tree gets shorter;
+        if self.root.keys.is_empty() && !self.root.is_leaf() {
+            self.root = self.root.children.pop().unwrap();
+        }
+        removed
+    }
+
+    // In-order iterator of the key-value pairs in a range, e.g. `btree.range(3..8)`;
+    pub fn range<R>(&self, range: R) -> Iter<'_, K, V>
+    where
+        R: RangeBounds<K>,
+        K: Clone,
+    {
+        let end = match range.end_bound() {
+            Bound::Included(k) => Bound::Included(k.clone()),
+            Bound::Excluded(k) => Bound::Excluded(k.clone()),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        let mut itr = Iter {
+            stack: Vec::new(),
+            end,
+        };
+        itr.seek(&self.root, range.start_bound());
+        itr
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        let mut itr = Iter {
+            stack: Vec::new(),
+            end: Bound::Unbounded,
+        };
+        itr.seek(&self.root, Bound::Unbounded);
+        itr
+    }
+}
+
+impl<K: Ord, V> Default for BTree<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// In-order iterator in the explicit-stack style of bst1::Iter: an entry (node, i) on the
+// stack means keys[i] of node is the next one to yield from that node, after everything above
+// it on the stack (that's children[i]) is done.
+pub struct Iter<'a, K, V> {
+    stack: Vec<(&'a Node<K, V>, usize)>,
+    end: Bound<K>,
+}
+
+impl<'a, K: Ord, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (node, i) = self.stack.last_mut()?;
+            let node: &'a Node<K, V> = node;
+            if *i == node.keys.len() {
+                self.stack.pop();
+                continue;
+            }
+
+            let key = &node.keys[*i];
+            let past_end = match &self.end {
+                Bound::Included(end) => key > end,
+                Bound::Excluded(end) => key >= end,
+                Bound::Unbounded => false,
+            };
+            if past_end {
+                self.stack.clear();
+                return None;
+            }
+
+            let val = &node.vals[*i];
+            *i += 1;
+            if !node.is_leaf() {
+                let child = &node.children[*i];
+                self.seek(child, Bound::Unbounded);
+            }
+            return Some((key, val));
+        }
+    }
+}
+
+impl<'a, K: Ord, V> Iter<'a, K, V> {
+    // Go down from `node` to the first key at or after `start`, pushing the way onto the stack;
+    fn seek(&mut self, mut node: &'a Node<K, V>, start: Bound<&K>) {
+        loop {
+            let (i, found) = match start {
+                Bound::Unbounded => (0, false),
+                Bound::Included(k) => match node.keys.binary_search(k) {
+                    Ok(i) => (i, true),
+                    Err(i) => (i, false),
+                },
+                Bound::Excluded(k) => match node.keys.binary_search(k) {
+                    Ok(i) => (i + 1, false),
+                    Err(i) => (i, false),
+                },
+            };
+            self.stack.push((node, i));
+            //if the start key itself is found, it's the next one, nothing on its left matters;
+            if found || node.is_leaf() {
+                break;
+            }
+            node = &node.children[i];
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BTree, Node};
+    use crate::prng::Prng;
+    use std::collections::BTreeMap;
+    use std::ops::Bound;
+
+    // check the B-tree properties, return the depth of the leaves;
+    fn check<K: Ord, V>(node: &Node<K, V>, t: usize, is_root: bool) -> usize {
+        assert!(node.keys.len() < 2 * t);
+        if !is_root {
+            assert!(node.keys.len() >= t - 1);
+        }
+        assert_eq!(node.keys.len(), node.vals.len());
+        assert!(node.keys.windows(2).all(|w| w[0] < w[1]));
+        if node.is_leaf() {
+            return 0;
+        }
+
+        assert_eq!(node.children.len(), node.keys.len() + 1);
+        let mut depth = None;
+        for (i, child) in node.children.iter().enumerate() {
+            if i > 0 {
+                assert!(child.keys.iter().all(|k| *k > node.keys[i - 1]));
+            }
+            if i < node.keys.len() {
+                assert!(child.keys.iter().all(|k| *k < node.keys[i]));
+            }
+            let d = check(child, t, false);
+            assert_eq!(*depth.get_or_insert(d), d);
+        }
+        depth.unwrap() + 1
+    }
+
+    #[test]
+    fn test_basics() {
+        let mut bt: BTree<i32, String> = BTree::with_min_degree(2);
+        for k in [10, 20, 5, 6, 12, 30, 7, 17, 3, 1, 2].iter() {
+            assert_eq!(bt.insert(*k, k.to_string()), None);
+        }
+        check(&bt.root, 2, true);
+        assert_eq!(bt.len(), 11);
+
+        assert_eq!(bt.insert(6, "six".to_string()), Some("6".to_string()));
+        assert_eq!(bt.get(&6).map(|s| s.as_str()), Some("six"));
+        assert_eq!(bt.get(&4), None);
+        if let Some(v) = bt.get_mut(&7) {
+            v.push('!');
+        }
+        assert_eq!(bt.get(&7).map(|s| s.as_str()), Some("7!"));
+
+        let c: Vec<&i32> = bt.iter().map(|(k, _)| k).collect();
+        assert_eq!(c, vec![&1, &2, &3, &5, &6, &7, &10, &12, &17, &20, &30]);
+
+        let c: Vec<&i32> = bt.range(5..17).map(|(k, _)| k).collect();
+        assert_eq!(c, vec![&5, &6, &7, &10, &12]);
+        let c: Vec<&i32> = bt.range(4..=17).map(|(k, _)| k).collect();
+        assert_eq!(c, vec![&5, &6, &7, &10, &12, &17]);
+        let c: Vec<&i32> = bt
+            .range((Bound::Excluded(20), Bound::Unbounded))
+            .map(|(k, _)| k)
+            .collect();
+        assert_eq!(c, vec![&30]);
+
+        assert_eq!(bt.remove(&6), Some((6, "six".to_string())));
+        assert_eq!(bt.remove(&6), None);
+        check(&bt.root, 2, true);
+        for k in [10, 20, 5, 12, 30, 7, 17, 3, 1, 2].iter() {
+            assert!(bt.remove(k).is_some());
+            check(&bt.root, 2, true);
+        }
+        assert!(bt.is_empty());
+        assert!(bt.root.is_leaf());
+        assert_eq!(bt.iter().next(), None);
+    }
+
+    // Randomized differential test against std::collections::BTreeMap;
+    #[test]
+    fn test_against_std() {
+        for t in [2, 3, 6].iter() {
+            let mut rng = Prng::new(30 + *t as u64);
+            let mut bt: BTree<u32, u32> = BTree::with_min_degree(*t);
+            let mut std_bt: BTreeMap<u32, u32> = BTreeMap::new();
+
+            for round in 0..20000 {
+                let key = rng.gen_range(1000) as u32;
+                match rng.gen_range(4) {
+                    0 | 1 => assert_eq!(bt.insert(key, round), std_bt.insert(key, round)),
+                    2 => assert_eq!(bt.remove(&key), std_bt.remove_entry(&key)),
+                    _ => assert_eq!(bt.get(&key), std_bt.get(&key)),
+                }
+                assert_eq!(bt.len(), std_bt.len());
+
+                if round % 1000 == 0 {
+                    check(&bt.root, *t, true);
+                    assert!(bt.iter().eq(std_bt.iter()));
+                }
+
+                if round % 100 == 0 {
+                    let a = rng.gen_range(1000) as u32;
+                    let b = a + rng.gen_range(100) as u32;
+                    assert!(bt.range(a..b).eq(std_bt.range(a..b)));
+                    assert!(bt.range(a..=b).eq(std_bt.range(a..=b)));
+                    assert!(bt.range(..a).eq(std_bt.range(..a)));
+                    let r = (Bound::Excluded(a), Bound::Included(b));
+                    assert!(bt.range(r).eq(std_bt.range(r)));
+                }
+            }
+            check(&bt.root, *t, true);
+            assert!(bt.iter().eq(std_bt.iter()));
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use std::ops::{AddAssign, Range, Sub};
+
+// A Fenwick tree (binary indexed tree) for prefix sums: both `add` and `prefix_sum` are
+// O(log(n)), in a plain array of n slots, no pointers at all.
+//
+// Using 1-based positions, slot i holds the sum of the elements (i - lowbit(i), i], where
+// lowbit(i) = i & (-i) is the lowest set bit of i. So a prefix [0, i) is the sum of the slots
+// i, i - lowbit(i), ... (clearing the lowest bit each time), and an element at position i is
+// covered by the slots i, i + lowbit(i), ... (adding the lowest bit each time).
+pub struct FenwickTree<T> {
+    tree: Vec<T>,
+}
+
+fn lowbit(i: usize) -> usize {
+    i & i.wrapping_neg()
+}
+
+impl<T> FenwickTree<T>
+where
+    T: Copy + Default + AddAssign + Sub<Output = T>,
+{
+    // n elements, all zero (T::default());
+    pub fn new(n: usize) -> Self {
+        FenwickTree {
+            tree: vec![T::default(); n],
+        }
+    }
+
+    // O(n): put every slot's sum into its parent slot once it's complete;
+    pub fn from_slice(vals: &[T]) -> Self {
+        let mut tree = vals.to_vec();
+        for i in 1..=tree.len() {
+            let parent = i + lowbit(i);
+            if parent <= tree.len() {
+                let v = tree[i - 1];
+                tree[parent - 1] += v;
+            }
+        }
+        FenwickTree { tree }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    // add `delta` to the element at `i`;
+    pub fn add(&mut self, i: usize, delta: T) {
+        assert!(i < self.tree.len(), "FenwickTree::add: index out of range");
+        let mut i = i + 1;
+        while i <= self.tree.len() {
+            self.tree[i - 1] += delta;
+            i += lowbit(i);
+        }
+    }
+
+    // sum of the elements in [0, end);
+    pub fn prefix_sum(&self, end: usize) -> T {
+        assert!(
+            end <= self.tree.len(),
+            "FenwickTree::prefix_sum: out of range"
+        );
+        let mut sum = T::default();
+        let mut i = end;
+        while i > 0 {
+            sum += self.tree[i - 1];
+            i -= lowbit(i);
+        }
+        sum
+    }
+
+    // sum of the elements in `range`;
+    pub fn range_sum(&self, range: Range<usize>) -> T {
+        if range.start >= range.end {
+            return T::default();
+        }
+        self.prefix_sum(range.end) - self.prefix_sum(range.start)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::FenwickTree;
+    use crate::prng::Prng;
+
+    #[test]
+    fn test_basics() {
+        let mut ft: FenwickTree<i64> = FenwickTree::from_slice(&[5, 3, 8, 6, 1, 4, 7, 2]);
+        assert_eq!(ft.len(), 8);
+        assert_eq!(ft.prefix_sum(0), 0);
+        assert_eq!(ft.prefix_sum(3), 16);
+        assert_eq!(ft.prefix_sum(8), 36);
+        assert_eq!(ft.range_sum(2..5), 15);
+        assert_eq!(ft.range_sum(5..5), 0);
+
+        ft.add(4, 10);
+        assert_eq!(ft.range_sum(2..5), 25);
+        assert_eq!(ft.range_sum(5..8), 13);
+        assert_eq!(ft.prefix_sum(8), 46);
+
+        let mut ft: FenwickTree<f64> = FenwickTree::new(3);
+        ft.add(1, 0.5);
+        ft.add(2, 1.5);
+        assert_eq!(ft.prefix_sum(3), 2.0);
+    }
+
+    #[test]
+    fn test_random() {
+        let mut rng = Prng::new(32);
+        let n = 200;
+        let mut vals: Vec<i64> = (0..n).map(|_| rng.gen_range(100) as i64).collect();
+        let mut ft = FenwickTree::from_slice(&vals);
+
+        for _ in 0..5000 {
+            let a = rng.gen_range(n);
+            if rng.gen_range(2) == 0 {
+                let delta = rng.gen_range(100) as i64 - 50;
+                vals[a] += delta;
+                ft.add(a, delta);
+            } else {
+                let b = a + rng.gen_range(n - a + 1);
+                assert_eq!(ft.range_sum(a..b), vals[a..b].iter().sum::<i64>());
+            }
+        }
+    }
+}
@@ -0,0 +1,448 @@
+use std::iter::FromIterator;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+// Which of two elements should come out of the heap first;
+pub trait Compare<T> {
+    // true if `a` has a strictly higher priority than `b`;
+    fn higher(a: &T, b: &T) -> bool;
+}
+
+// the greatest first, like std::collections::BinaryHeap;
+pub struct MaxOrder;
+
+// the least first, e.g. for Dijkstra's shortest paths or timers;
+pub struct MinOrder;
+
+impl<T: Ord> Compare<T> for MaxOrder {
+    fn higher(a: &T, b: &T) -> bool {
+        a > b
+    }
+}
+
+impl<T: Ord> Compare<T> for MinOrder {
+    fn higher(a: &T, b: &T) -> bool {
+        a < b
+    }
+}
+
+// A stable handle to an element pushed into the heap, valid until the element leaves the heap;
+// the element moves around in the array, but the handle keeps pointing at it. A handle of an
+// element already gone is detected by the generation, even if its slot has been reused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Handle {
+    slot: usize,
+    gen: u32,
+}
+
+struct Slot {
+    //index of the element in `data`, None when the slot is free;
+    pos: Option<usize>,
+    gen: u32,
+}
+
+// A binary heap in an implicit array: the children of data[i] are data[2i+1] and data[2i+2],
+// and the parent of data[i] is data[(i-1)/2]; every element has a priority no lower than its
+// children's, so the top is data[0].
+//
+// `slots[owners[i]]` is the handle slot of data[i], and points back at i; every move of an
+// element in `data` updates its slot.
+pub struct BinaryHeap<T, C = MaxOrder> {
+    data: Vec<T>,
+    owners: Vec<usize>,
+    slots: Vec<Slot>,
+    free_slots: Vec<usize>,
+    _cmp: PhantomData<C>,
+}
+
+impl<T, C: Compare<T>> BinaryHeap<T, C> {
+    pub fn new() -> Self {
+        BinaryHeap {
+            data: Vec::new(),
+            owners: Vec::new(),
+            slots: Vec::new(),
+            free_slots: Vec::new(),
+            _cmp: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn push(&mut self, val: T) -> Handle {
+        let handle = self.alloc_slot(self.data.len());
+        self.data.push(val);
+        self.owners.push(handle.slot);
+        self.sift_up(self.data.len() - 1);
+        handle
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if self.data.is_empty() {
+            return None;
+        }
+        Some(self.remove_at(0))
+    }
+
+    pub fn peek(&self) -> Option<&T> {
+        self.data.first()
+    }
+
+    // A mutable ref to the top through a guard, the heap is fixed up when the guard drops;
+    pub fn peek_mut(&mut self) -> Option<PeekMut<'_, T, C>> {
+        if self.data.is_empty() {
+            None
+        } else {
+            Some(PeekMut { heap: self })
+        }
+    }
+
+    pub fn get(&self, handle: Handle) -> Option<&T> {
+        self.pos_of(handle).map(|i| &self.data[i])
+    }
+
+    pub fn contains(&self, handle: Handle) -> bool {
+        self.pos_of(handle).is_some()
+    }
+
+    // Raise the priority of an element to `val` (with MinOrder, that's the classic decrease-key
+    // of Dijkstra's and Prim's algorithms). Only sifts up, O(log(n)). Panics if `val` has a
+    // lower priority than the current value, use `update` for that;
+    pub fn decrease_key(&mut self, handle: Handle, val: T) {
+        let i = self
+            .pos_of(handle)
+            .expect("BinaryHeap::decrease_key: stale handle");
+        assert!(
+            !C::higher(&self.data[i], &val),
+            "BinaryHeap::decrease_key: lower priority"
+        );
+        self.data[i] = val;
+        self.sift_up(i);
+    }
+
+    // Change the value of an element to anything, return the old value; None if the handle is
+    // stale;
+    pub fn update(&mut self, handle: Handle, val: T) -> Option<T> {
+        let i = self.pos_of(handle)?;
+        let old = std::mem::replace(&mut self.data[i], val);
+        let i = self.sift_up(i);
+        self.sift_down(i, self.data.len());
+        Some(old)
+    }
+
+    // Remove an element by its handle, e.g. to cancel a scheduled job;
+    pub fn remove(&mut self, handle: Handle) -> Option<T> {
+        let i = self.pos_of(handle)?;
+        Some(self.remove_at(i))
+    }
+
+    // Heap sort in place: the top goes to the end, and so on; so the result is sorted by
+    // priority with the highest last: ascending for MaxOrder, the same as std's;
+    pub fn into_sorted_vec(mut self) -> Vec<T> {
+        let mut end = self.data.len();
+        while end > 1 {
+            end -= 1;
+            self.swap(0, end);
+            self.sift_down(0, end);
+        }
+        self.data
+    }
+
+    // The elements in the internal (heap) order;
+    pub fn into_vec(self) -> Vec<T> {
+        self.data
+    }
+
+    fn alloc_slot(&mut self, pos: usize) -> Handle {
+        match self.free_slots.pop() {
+            Some(slot) => {
+                self.slots[slot].pos = Some(pos);
+                Handle {
+                    slot,
+                    gen: self.slots[slot].gen,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    pos: Some(pos),
+                    gen: 0,
+                });
+                Handle {
+                    slot: self.slots.len() - 1,
+                    gen: 0,
+                }
+            }
+        }
+    }
+
+    fn pos_of(&self, handle: Handle) -> Option<usize> {
+        let slot = self.slots.get(handle.slot)?;
+        if slot.gen != handle.gen {
+            return None;
+        }
+        slot.pos
+    }
+
+    // move the last element to i, and fix it up or down;
+    fn remove_at(&mut self, i: usize) -> T {
+        let last = self.data.len() - 1;
+        self.swap(i, last);
+
+        let slot = self.owners.pop().unwrap();
+        self.slots[slot].pos = None;
+        self.slots[slot].gen = self.slots[slot].gen.wrapping_add(1);
+        self.free_slots.push(slot);
+        let val = self.data.pop().unwrap();
+
+        if i < self.data.len() {
+            let i = self.sift_up(i);
+            self.sift_down(i, self.data.len());
+        }
+        val
+    }
+
+    fn swap(&mut self, i: usize, j: usize) {
+        self.data.swap(i, j);
+        self.owners.swap(i, j);
+        self.slots[self.owners[i]].pos = Some(i);
+        self.slots[self.owners[j]].pos = Some(j);
+    }
+
+    // move data[i] up while it's higher than its parent; return where it stops;
+    fn sift_up(&mut self, mut i: usize) -> usize {
+        while i > 0 {
+            let parent = (i - 1) / 2;
+            if !C::higher(&self.data[i], &self.data[parent]) {
+                break;
+            }
+            self.swap(i, parent);
+            i = parent;
+        }
+        i
+    }
+
+    // move data[i] down while one of its children (within data[..end]) is higher;
+    fn sift_down(&mut self, mut i: usize, end: usize) {
+        loop {
+            let mut highest = i;
+            for child in [2 * i + 1, 2 * i + 2].iter() {
+                if *child < end && C::higher(&self.data[*child], &self.data[highest]) {
+                    highest = *child;
+                }
+            }
+            if highest == i {
+                break;
+            }
+            self.swap(i, highest);
+            i = highest;
+        }
+    }
+}
+
+impl<T, C: Compare<T>> Default for BinaryHeap<T, C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Heapify in O(n): sift down every non-leaf element, from the last one up to the root; most
+// elements are near the bottom and move little, so the total work is linear instead of the
+// O(n*log(n)) of pushing one by one.
+impl<T, C: Compare<T>> FromIterator<T> for BinaryHeap<T, C> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut heap = BinaryHeap::new();
+        for val in iter {
+            let handle = heap.alloc_slot(heap.data.len());
+            heap.data.push(val);
+            heap.owners.push(handle.slot);
+        }
+        let n = heap.data.len();
+        for i in (0..n / 2).rev() {
+            heap.sift_down(i, n);
+        }
+        heap
+    }
+}
+
+pub struct PeekMut<'a, T, C: Compare<T>> {
+    heap: &'a mut BinaryHeap<T, C>,
+}
+
+impl<'a, T, C: Compare<T>> Deref for PeekMut<'a, T, C> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.heap.data[0]
+    }
+}
+
+impl<'a, T, C: Compare<T>> DerefMut for PeekMut<'a, T, C> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.heap.data[0]
+    }
+}
+
+// the top may have been lowered through DerefMut, put it back in place;
+impl<'a, T, C: Compare<T>> Drop for PeekMut<'a, T, C> {
+    fn drop(&mut self) {
+        let n = self.heap.data.len();
+        self.heap.sift_down(0, n);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BinaryHeap, Compare, MinOrder};
+    use crate::prng::Prng;
+
+    // check the heap property and the handle slots;
+    fn check<T, C: Compare<T>>(heap: &BinaryHeap<T, C>) {
+        for i in 1..heap.data.len() {
+            assert!(!C::higher(&heap.data[i], &heap.data[(i - 1) / 2]));
+        }
+        for (i, slot) in heap.owners.iter().enumerate() {
+            assert_eq!(heap.slots[*slot].pos, Some(i));
+        }
+    }
+
+    #[test]
+    fn test_basics() {
+        let mut heap: BinaryHeap<i32> = BinaryHeap::new();
+        assert_eq!(heap.peek(), None);
+        assert_eq!(heap.pop(), None);
+
+        for val in [5, 1, 8, 3, 9, 2].iter() {
+            heap.push(*val);
+        }
+        check(&heap);
+        assert_eq!(heap.len(), 6);
+        assert_eq!(heap.peek(), Some(&9));
+
+        assert_eq!(heap.pop(), Some(9));
+        assert_eq!(heap.pop(), Some(8));
+        check(&heap);
+        assert_eq!(heap.into_sorted_vec(), vec![1, 2, 3, 5]);
+
+        let mut heap: BinaryHeap<i32, MinOrder> = vec![5, 1, 8, 3, 9, 2].into_iter().collect();
+        check(&heap);
+        let mut c = Vec::new();
+        while let Some(v) = heap.pop() {
+            c.push(v);
+        }
+        assert_eq!(c, vec![1, 2, 3, 5, 8, 9]);
+    }
+
+    #[test]
+    fn test_peek_mut() {
+        let mut heap: BinaryHeap<i32> = vec![5, 1, 8, 3].into_iter().collect();
+        if let Some(mut top) = heap.peek_mut() {
+            assert_eq!(*top, 8);
+            *top = 0;
+        }
+        check(&heap);
+        assert_eq!(heap.peek(), Some(&5));
+        assert_eq!(heap.into_sorted_vec(), vec![0, 1, 3, 5]);
+    }
+
+    #[test]
+    fn test_handles() {
+        let mut heap: BinaryHeap<u32, MinOrder> = BinaryHeap::new();
+        let a = heap.push(50);
+        let b = heap.push(40);
+        let c = heap.push(30);
+        assert_eq!(heap.get(a), Some(&50));
+
+        heap.decrease_key(a, 10);
+        check(&heap);
+        assert_eq!(heap.peek(), Some(&10));
+        assert_eq!(heap.get(a), Some(&10));
+
+        assert_eq!(heap.update(c, 60), Some(30));
+        check(&heap);
+        assert_eq!(heap.remove(b), Some(40));
+        assert_eq!(heap.remove(b), None);
+        check(&heap);
+
+        assert_eq!(heap.pop(), Some(10));
+        //a's slot may be reused, but the old handle stays stale;
+        let d = heap.push(20);
+        assert!(!heap.contains(a));
+        assert_eq!(heap.get(d), Some(&20));
+        assert_eq!(heap.update(a, 1), None);
+        assert_eq!(heap.into_sorted_vec(), vec![60, 20]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_decrease_key_wrong_way() {
+        let mut heap: BinaryHeap<u32, MinOrder> = BinaryHeap::new();
+        let a = heap.push(5);
+        heap.decrease_key(a, 6);
+    }
+
+    // Dijkstra's shortest paths, the classic user of decrease-key;
+    #[test]
+    fn test_dijkstra() {
+        let edges: Vec<Vec<(usize, u32)>> = vec![
+            vec![(1, 7), (2, 9), (5, 14)],
+            vec![(0, 7), (2, 10), (3, 15)],
+            vec![(0, 9), (1, 10), (3, 11), (5, 2)],
+            vec![(1, 15), (2, 11), (4, 6)],
+            vec![(3, 6), (5, 9)],
+            vec![(0, 14), (2, 2), (4, 9)],
+        ];
+
+        let mut dist = vec![u32::MAX; edges.len()];
+        let mut heap: BinaryHeap<(u32, usize), MinOrder> = BinaryHeap::new();
+        let mut handles = vec![None; edges.len()];
+        dist[0] = 0;
+        handles[0] = Some(heap.push((0, 0)));
+
+        while let Some((d, u)) = heap.pop() {
+            for (v, w) in edges[u].iter() {
+                if d + w < dist[*v] {
+                    dist[*v] = d + w;
+                    match handles[*v].filter(|h| heap.contains(*h)) {
+                        Some(h) => heap.decrease_key(h, (d + w, *v)),
+                        None => handles[*v] = Some(heap.push((d + w, *v))),
+                    }
+                }
+            }
+        }
+        assert_eq!(dist, vec![0, 7, 9, 20, 20, 11]);
+    }
+
+    #[test]
+    fn test_random() {
+        let mut rng = Prng::new(33);
+        let vals: Vec<u64> = (0..1000).map(|_| rng.next_u64() % 500).collect();
+        let heap: BinaryHeap<u64> = vals.iter().cloned().collect();
+        check(&heap);
+        let mut sorted = vals.clone();
+        sorted.sort();
+        assert_eq!(heap.into_sorted_vec(), sorted);
+
+        let mut heap: BinaryHeap<u64> = BinaryHeap::new();
+        let mut handles = Vec::new();
+        for v in vals.iter() {
+            handles.push(heap.push(*v));
+        }
+        for h in handles.iter().step_by(3) {
+            heap.update(*h, rng.next_u64() % 500);
+        }
+        for h in handles.iter().skip(1).step_by(5) {
+            heap.remove(*h);
+        }
+        check(&heap);
+        let mut prev = u64::MAX;
+        while let Some(v) = heap.pop() {
+            assert!(v <= prev);
+            prev = v;
+        }
+    }
+}
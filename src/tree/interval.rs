@@ -0,0 +1,415 @@
+use std::ops::{Deref, Range};
+
+// An interval tree: a BST ordered by the intervals (by start, then by end), where every node is
+// augmented with `max`, the max end of all the intervals in its subtree. With `max` we can skip
+// a whole subtree when looking for overlaps: if subtree.max <= a, nothing in it can overlap [a, b).
+//
+// All the intervals are half open: [start, end).
+pub struct Node<K, V> {
+    range: Range<K>,
+    val: V,
+    max: K,
+    left: Link<K, V>,
+    right: Link<K, V>,
+}
+
+type Link<K, V> = Option<Box<Node<K, V>>>;
+
+pub struct IntervalTree<K, V> {
+    root: Link<K, V>,
+}
+
+impl<K: Ord + Clone, V> IntervalTree<K, V> {
+    pub fn new() -> Self {
+        IntervalTree { root: None }
+    }
+
+    // Panics if the interval is empty (start >= end), which overlaps nothing. The same interval
+    // can be inserted more than once, the same as BSTree;
+    pub fn insert(&mut self, range: Range<K>, val: V) {
+        assert!(
+            range.start < range.end,
+            "IntervalTree::insert: empty interval"
+        );
+
+        //the same as BSTree::insert(), except that the `max` of every node on the way down is
+        //updated, because the new interval will be in its subtree;
+        let mut target_place: &mut Link<K, V> = &mut self.root;
+        while let Some(boxed_node) = target_place {
+            if boxed_node.max < range.end {
+                boxed_node.max = range.end.clone();
+            }
+            target_place = if less_eq(&range, &boxed_node.range) {
+                &mut boxed_node.left
+            } else {
+                &mut boxed_node.right
+            }
+        }
+
+        *target_place = Some(Box::new(Node {
+            max: range.end.clone(),
+            range,
+            val,
+            left: None,
+            right: None,
+        }));
+    }
+
+    // Remove one interval equal to `range`, return its value.
+    //
+    // The `max` of every node on the path has to be fixed after the removal, bottom-up; but the
+    // tree isn't balanced, so the path may be as long as the tree, too long for recursion. So the
+    // path is taken apart on the way down: each node is detached from its child on the path and
+    // kept on a stack, then the nodes are put back together from the bottom, fixing `max` as they
+    // go.
+    pub fn remove(&mut self, range: &Range<K>) -> Option<V> {
+        // (node, whether the path goes on to its left child);
+        let mut path: Vec<(Box<Node<K, V>>, bool)> = Vec::new();
+        let mut link = self.root.take();
+        let mut removed = None;
+        while let Some(mut boxed_node) = link {
+            if boxed_node.range == *range {
+                link = match (boxed_node.left.take(), boxed_node.right.take()) {
+                    (None, right) => right,
+                    (left, None) => left,
+                    (left, Some(right)) => {
+                        //two children: the successor (min of the right subtree) takes the place;
+                        let (mut succ, right) = remove_min(right);
+                        succ.left = left;
+                        succ.right = right;
+                        update_max(&mut succ);
+                        Some(succ)
+                    }
+                };
+                removed = Some(boxed_node.val);
+                break;
+            }
+            let go_left = less_eq(range, &boxed_node.range);
+            link = if go_left {
+                boxed_node.left.take()
+            } else {
+                boxed_node.right.take()
+            };
+            path.push((boxed_node, go_left));
+        }
+
+        self.root = reattach(path, link);
+        removed
+    }
+
+    // All the intervals overlapping [range.start, range.end), ordered by start;
+    pub fn overlapping(&self, range: Range<K>) -> OverlapItr<'_, K, V> {
+        OverlapItr::new(&self.root, range, false)
+    }
+
+    // All the intervals containing `point`, ordered by start;
+    pub fn stabbing(&self, point: K) -> OverlapItr<'_, K, V> {
+        let range = Range {
+            start: point.clone(),
+            end: point,
+        };
+        OverlapItr::new(&self.root, range, true)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+}
+
+impl<K: Ord + Clone, V> Default for IntervalTree<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn less_eq<K: Ord>(a: &Range<K>, b: &Range<K>) -> bool {
+    (&a.start, &a.end) <= (&b.start, &b.end)
+}
+
+// recompute `max` of a node from its own end and its children's `max`;
+fn update_max<K: Ord + Clone, V>(node: &mut Node<K, V>) {
+    let mut max = &node.range.end;
+    for boxed_node in [&node.left, &node.right].iter().copied().flatten() {
+        if *max < boxed_node.max {
+            max = &boxed_node.max;
+        }
+    }
+    node.max = max.clone();
+}
+
+// Put a path taken apart by remove() back together, bottom-up: `link` is the new subtree below
+// the last node of the path, and every node gets its `max` fixed once its child is back;
+fn reattach<K: Ord + Clone, V>(
+    mut path: Vec<(Box<Node<K, V>>, bool)>,
+    mut link: Link<K, V>,
+) -> Link<K, V> {
+    while let Some((mut boxed_node, left)) = path.pop() {
+        if left {
+            boxed_node.left = link;
+        } else {
+            boxed_node.right = link;
+        }
+        update_max(&mut boxed_node);
+        link = Some(boxed_node);
+    }
+    link
+}
+
+// Detach the min node of a subtree, return it and what's left of the subtree; the left spine is
+// taken apart and put back the same way as in remove();
+fn remove_min<K: Ord + Clone, V>(mut node: Box<Node<K, V>>) -> (Box<Node<K, V>>, Link<K, V>) {
+    let mut path: Vec<(Box<Node<K, V>>, bool)> = Vec::new();
+    while let Some(left) = node.left.take() {
+        path.push((node, true));
+        node = left;
+    }
+    let rest = reattach(path, node.right.take());
+    (node, rest)
+}
+
+// The default drop of Box is recursive, which overflows the stack on a degenerate tree, e.g. one
+// built from sorted intervals; so take the nodes apart with an explicit stack, as BSTree does.
+impl<K, V> Drop for IntervalTree<K, V> {
+    fn drop(&mut self) {
+        let mut stack: Vec<Box<Node<K, V>>> = Vec::new();
+        if let Some(boxed_node) = self.root.take() {
+            stack.push(boxed_node);
+        }
+        while let Some(mut boxed_node) = stack.pop() {
+            if let Some(left) = boxed_node.left.take() {
+                stack.push(left);
+            }
+            if let Some(right) = boxed_node.right.take() {
+                stack.push(right);
+            }
+            //`boxed_node` has no children now and is dropped here;
+        }
+    }
+}
+
+// A lazy in-order iterator in the explicit-stack style of bst1::Iter, with 2 prunings:
+//     1. a subtree whose `max` <= query.start is not pushed at all;
+//     2. once a node with start >= query.end is popped, all the following ones in order start
+//        even later, so the iteration stops;
+// With `closed` the query is the single point query.start (query.end is the same point), and the
+// conditions become end > point and start <= point.
+pub struct OverlapItr<'a, K, V> {
+    stack: Vec<&'a Node<K, V>>,
+    query: Range<K>,
+    closed: bool,
+}
+
+impl<'a, K: Ord, V> Iterator for OverlapItr<'a, K, V> {
+    type Item = (&'a Range<K>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.stack.pop() {
+            let started_too_late = if self.closed {
+                node.range.start > self.query.end
+            } else {
+                node.range.start >= self.query.end
+            };
+            if started_too_late {
+                self.stack.clear();
+                return None;
+            }
+
+            self.push_till_next(&node.right);
+            if node.range.end > self.query.start {
+                return Some((&node.range, &node.val));
+            }
+        }
+        None
+    }
+}
+
+impl<'a, K: Ord, V> OverlapItr<'a, K, V> {
+    fn new(link: &'a Link<K, V>, query: Range<K>, closed: bool) -> Self {
+        let empty = !closed && query.start >= query.end;
+        let mut itr = OverlapItr {
+            stack: Vec::new(),
+            query,
+            closed,
+        };
+        if !empty {
+            itr.push_till_next(link);
+        }
+        itr
+    }
+
+    fn push_till_next(&mut self, mut link: &'a Link<K, V>) {
+        while let Some(boxed_node) = link {
+            if boxed_node.max <= self.query.start {
+                break;
+            }
+            self.stack.push(boxed_node.deref());
+            link = &boxed_node.left;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{IntervalTree, Link, Node};
+    use crate::prng::Prng;
+    use std::ops::Range;
+
+    // check that every node's `max` is correct, return it;
+    fn check_max(link: &Link<u32, usize>) -> Option<u32> {
+        link.as_ref().map(|node| {
+            let mut max = node.range.end;
+            for m in [check_max(&node.left), check_max(&node.right)]
+                .iter()
+                .flatten()
+            {
+                max = max.max(*m);
+            }
+            assert_eq!(node.max, max);
+            max
+        })
+    }
+
+    #[test]
+    fn test_overlapping() {
+        let mut it: IntervalTree<u32, &str> = IntervalTree::new();
+        it.insert(15..20, "a");
+        it.insert(10..30, "b");
+        it.insert(17..19, "c");
+        it.insert(5..20, "d");
+        it.insert(12..15, "e");
+        it.insert(30..40, "f");
+
+        let c: Vec<&str> = it.overlapping(14..16).map(|(_, v)| *v).collect();
+        assert_eq!(c, vec!["d", "b", "e", "a"]);
+
+        //half open: [30, 40) does not overlap [20, 30), and [15, 20) does not overlap [20, 25);
+        let c: Vec<&str> = it.overlapping(20..30).map(|(_, v)| *v).collect();
+        assert_eq!(c, vec!["b"]);
+
+        let c: Vec<Range<u32>> = it.overlapping(0..100).map(|(r, _)| r.clone()).collect();
+        assert_eq!(c, vec![5..20, 10..30, 12..15, 15..20, 17..19, 30..40]);
+
+        assert_eq!(it.overlapping(40..50).count(), 0);
+        assert_eq!(it.overlapping(16..16).count(), 0);
+
+        let c: Vec<&str> = it.stabbing(15).map(|(_, v)| *v).collect();
+        assert_eq!(c, vec!["d", "b", "a"]);
+        let c: Vec<&str> = it.stabbing(30).map(|(_, v)| *v).collect();
+        assert_eq!(c, vec!["f"]);
+        assert_eq!(it.stabbing(4).count(), 0);
+
+        assert_eq!(it.remove(&(10..30)), Some("b"));
+        assert_eq!(it.remove(&(10..30)), None);
+        let c: Vec<&str> = it.stabbing(25).map(|(_, v)| *v).collect();
+        assert!(c.is_empty());
+    }
+
+    #[test]
+    fn test_random() {
+        let mut rng = Prng::new(28);
+        let mut it: IntervalTree<u32, usize> = IntervalTree::new();
+        let mut all: Vec<Option<Range<u32>>> = Vec::new();
+
+        for i in 0..2000 {
+            let start = rng.gen_range(1000) as u32;
+            let len = 1 + rng.gen_range(50) as u32;
+            it.insert(start..start + len, i);
+            all.push(Some(start..start + len));
+
+            //remove a random one now and then;
+            if rng.gen_range(3) == 0 {
+                let j = rng.gen_range(all.len());
+                if let Some(r) = all[j].take() {
+                    assert!(it.remove(&r).is_some());
+                }
+            }
+        }
+        check_max(&it.root);
+
+        for _ in 0..200 {
+            let a = rng.gen_range(1100) as u32;
+            let b = a + 1 + rng.gen_range(30) as u32;
+
+            let mut got: Vec<(u32, u32)> = it
+                .overlapping(a..b)
+                .map(|(r, _)| (r.start, r.end))
+                .collect();
+            let mut expect: Vec<(u32, u32)> = all
+                .iter()
+                .flatten()
+                .filter(|r| r.start < b && a < r.end)
+                .map(|r| (r.start, r.end))
+                .collect();
+            //results come ordered by start already;
+            assert!(got.windows(2).all(|w| w[0] <= w[1]));
+            got.sort();
+            expect.sort();
+            assert_eq!(got, expect);
+
+            let mut got: Vec<(u32, u32)> = it.stabbing(a).map(|(r, _)| (r.start, r.end)).collect();
+            let mut expect: Vec<(u32, u32)> = all
+                .iter()
+                .flatten()
+                .filter(|r| r.start <= a && a < r.end)
+                .map(|r| (r.start, r.end))
+                .collect();
+            got.sort();
+            expect.sort();
+            assert_eq!(got, expect);
+        }
+    }
+
+    // Sorted inserts build a chain as deep as it's long, too deep for a recursive remove or drop;
+    // it's put together here directly, as insert() would take O(n^2) to build it;
+    #[test]
+    fn test_sorted_chain() {
+        let n = if cfg!(miri) { 1000 } else { 200_000 };
+        let mut root = None;
+        for i in (0..n).rev() {
+            root = Some(Box::new(Node {
+                range: i..i + 2,
+                val: i,
+                max: n + 1,
+                left: None,
+                right: root,
+            }));
+        }
+        let mut it = IntervalTree { root };
+        it.insert(n..n + 1, n);
+        assert_eq!(it.remove(&(n..n + 1)), Some(n));
+
+        //the last one is at the bottom; then the root, and one in the middle;
+        assert_eq!(it.remove(&(n - 1..n + 1)), Some(n - 1));
+        assert_eq!(it.remove(&(0..2)), Some(0));
+        assert_eq!(it.remove(&(n / 2..n / 2 + 2)), Some(n / 2));
+        assert_eq!(it.remove(&(n / 2..n / 2 + 2)), None);
+
+        //`max` is still right all along the chain: nothing past n is left to overlap;
+        assert_eq!(it.overlapping(n..n + 5).count(), 0);
+        let c: Vec<u32> = it.stabbing(n - 1).map(|(_, v)| *v).collect();
+        assert_eq!(c, vec![n - 2]);
+        let c: Vec<u32> = it.stabbing(n / 2).map(|(_, v)| *v).collect();
+        assert_eq!(c, vec![n / 2 - 1]);
+        drop(it);
+    }
+
+    // Removing nodes with 2 children, where the successor is deep down a left spine;
+    #[test]
+    fn test_remove_successor() {
+        let mut it: IntervalTree<u32, usize> = IntervalTree::new();
+        it.insert(1..2, 1);
+        it.insert(0..1, 0);
+        for i in (2..=100).rev() {
+            it.insert(i..i + 1, i as usize);
+        }
+        it.insert(1000..1010, 1000);
+        //the root 1 has the right child 100, whose left spine goes down to 2;
+        assert_eq!(it.remove(&(1..2)), Some(1));
+        check_max(&it.root);
+        assert_eq!(it.root.as_ref().map(|node| node.range.clone()), Some(2..3));
+        assert_eq!(it.remove(&(1000..1010)), Some(1000));
+        check_max(&it.root);
+        assert_eq!(it.stabbing(1005).count(), 0);
+        assert_eq!(it.overlapping(0..1000).count(), 100);
+    }
+}
@@ -1 +1,10 @@
 pub mod bst1;
+pub mod btree;
+pub mod fenwick;
+pub mod heap;
+pub mod interval;
+pub mod radix;
+pub mod scapegoat;
+pub mod segment;
+pub mod splay;
+pub mod treap;
@@ -0,0 +1,393 @@
+use std::mem;
+
+// A compressed radix tree (a.k.a. Patricia trie) over byte strings. Every edge is labeled with a
+// byte string instead of a single byte, and a node without value has at least 2 children, so a
+// chain of single-child nodes is collapsed into one edge:
+//
+//     insert "romane", "romanus", "romulus", "rubens":
+//
+//                 (root)
+//                 /    \
+//               "r"     ...
+//              /   \
+//           "om"   "ubens"*
+//          /    \
+//       "an"    "ulus"*
+//       /   \
+//     "e"*  "us"*
+//
+// The label is kept in the child node. The children of a node are sorted by the first byte of
+// their labels (two children never share a first byte), so a depth first walk yields the keys in
+// lexicographic order.
+struct Node<V> {
+    label: Vec<u8>,
+    val: Option<V>,
+    children: Vec<Node<V>>,
+}
+
+pub struct RadixTree<V> {
+    //the root has an empty label, and holds the value of the empty key;
+    root: Node<V>,
+    len: usize,
+}
+
+impl<V> Node<V> {
+    fn new(label: Vec<u8>, val: Option<V>) -> Self {
+        Node {
+            label,
+            val,
+            children: Vec::new(),
+        }
+    }
+
+    // the index of the child whose label starts with `byte`, or where it should be inserted;
+    fn find_child(&self, byte: u8) -> Result<usize, usize> {
+        self.children
+            .binary_search_by_key(&byte, |child| child.label[0])
+    }
+}
+
+fn common_prefix_len(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b.iter()).take_while(|(x, y)| x == y).count()
+}
+
+// Keys can be anything viewed as bytes: &str, String, &[u8], Vec<u8> ...
+impl<V> RadixTree<V> {
+    pub fn new() -> Self {
+        RadixTree {
+            root: Node::new(Vec::new(), None),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Insert a key-value pair; if the key exists, its value is replaced and the old one is
+    // returned;
+    pub fn insert<K: AsRef<[u8]>>(&mut self, key: K, val: V) -> Option<V> {
+        let mut node: &mut Node<V> = &mut self.root;
+        let mut rest: &[u8] = key.as_ref();
+
+        //loop invariant: the label of `node` is matched, `rest` is what's left of the key;
+        loop {
+            if rest.is_empty() {
+                let old = node.val.replace(val);
+                if old.is_none() {
+                    self.len += 1;
+                }
+                return old;
+            }
+
+            let i = match node.find_child(rest[0]) {
+                Ok(i) => i,
+                Err(i) => {
+                    node.children.insert(i, Node::new(rest.to_vec(), Some(val)));
+                    self.len += 1;
+                    return None;
+                }
+            };
+
+            let child = &mut node.children[i];
+            let common = common_prefix_len(&child.label, rest);
+            if common < child.label.len() {
+                //the key leaves the edge in the middle; split the edge there: `child` keeps the
+                //common part, and the rest of it moves down into a new node with all its stuff;
+                let tail = child.label.split_off(common);
+                let mut lower = Node::new(tail, child.val.take());
+                lower.children = mem::take(&mut child.children);
+                child.children.push(lower);
+            }
+            rest = &rest[common..];
+            node = child;
+        }
+    }
+
+    pub fn get<K: AsRef<[u8]>>(&self, key: K) -> Option<&V> {
+        let mut node: &Node<V> = &self.root;
+        let mut rest: &[u8] = key.as_ref();
+        while !rest.is_empty() {
+            node = &node.children[node.find_child(rest[0]).ok()?];
+            if !rest.starts_with(&node.label) {
+                return None;
+            }
+            rest = &rest[node.label.len()..];
+        }
+        node.val.as_ref()
+    }
+
+    pub fn contains_key<K: AsRef<[u8]>>(&self, key: K) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn remove<K: AsRef<[u8]>>(&mut self, key: K) -> Option<V> {
+        let removed = remove(&mut self.root, key.as_ref());
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    // The value of the longest key which is a prefix of `key`, together with the length of that
+    // prefix; e.g. with "10." and "10.1." inserted, "10.1.2.3" matches "10.1.", that's what a
+    // routing table lookup does;
+    pub fn longest_prefix<K: AsRef<[u8]>>(&self, key: K) -> Option<(usize, &V)> {
+        let mut node: &Node<V> = &self.root;
+        let mut rest: &[u8] = key.as_ref();
+        let mut matched: usize = 0;
+        let mut best = node.val.as_ref().map(|val| (0, val));
+
+        while !rest.is_empty() {
+            node = match node.find_child(rest[0]) {
+                Ok(i) => &node.children[i],
+                Err(_) => break,
+            };
+            if !rest.starts_with(&node.label) {
+                break;
+            }
+            rest = &rest[node.label.len()..];
+            matched += node.label.len();
+            if let Some(val) = &node.val {
+                best = Some((matched, val));
+            }
+        }
+        best
+    }
+
+    // All the keys starting with `prefix` (including `prefix` itself), in lexicographic order;
+    pub fn prefix_iter<K: AsRef<[u8]>>(&self, prefix: K) -> PrefixItr<'_, V> {
+        let mut itr = PrefixItr {
+            stack: Vec::new(),
+            key: Vec::new(),
+        };
+
+        //find the node where `prefix` ends, it may end in the middle of the node's label;
+        let mut node: &Node<V> = &self.root;
+        let mut rest: &[u8] = prefix.as_ref();
+        loop {
+            let common = common_prefix_len(&node.label, rest);
+            if common == rest.len() {
+                itr.stack.push((node, itr.key.len()));
+                return itr;
+            }
+            if common < node.label.len() {
+                return itr;
+            }
+
+            itr.key.extend_from_slice(&node.label);
+            rest = &rest[common..];
+            node = match node.find_child(rest[0]) {
+                Ok(i) => &node.children[i],
+                Err(_) => return itr,
+            };
+        }
+    }
+
+    pub fn iter(&self) -> PrefixItr<'_, V> {
+        self.prefix_iter(b"")
+    }
+}
+
+impl<V> Default for RadixTree<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Remove `rest` under `node` (whose label is matched). After removing from a child, compact
+// the child: drop it if it has neither value nor children, or merge it with its only child if
+// it has no value; so the "no single-child node without value" rule holds again.
+fn remove<V>(node: &mut Node<V>, rest: &[u8]) -> Option<V> {
+    if rest.is_empty() {
+        return node.val.take();
+    }
+
+    let i = node.find_child(rest[0]).ok()?;
+    let child = &mut node.children[i];
+    if !rest.starts_with(&child.label) {
+        return None;
+    }
+    let removed = remove(child, &rest[child.label.len()..])?;
+
+    if child.val.is_none() {
+        match child.children.len() {
+            0 => {
+                node.children.remove(i);
+            }
+            1 => {
+                let only = child.children.pop().unwrap();
+                child.label.extend_from_slice(&only.label);
+                child.val = only.val;
+                child.children = only.children;
+            }
+            _ => {}
+        }
+    }
+    Some(removed)
+}
+
+// A depth first walk in the explicit-stack style of bst1::Iter; the key of the current
+// node is rebuilt in `key`: an entry (node, n) on the stack means node's label follows the
+// first n bytes of `key`.
+pub struct PrefixItr<'a, V> {
+    stack: Vec<(&'a Node<V>, usize)>,
+    key: Vec<u8>,
+}
+
+impl<'a, V> Iterator for PrefixItr<'a, V> {
+    type Item = (Vec<u8>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((node, n)) = self.stack.pop() {
+            self.key.truncate(n);
+            self.key.extend_from_slice(&node.label);
+
+            //reversed, so that the smallest child is popped first;
+            for child in node.children.iter().rev() {
+                self.stack.push((child, self.key.len()));
+            }
+            if let Some(val) = &node.val {
+                return Some((self.key.clone(), val));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Node, RadixTree};
+    use crate::prng::Prng;
+    use std::collections::BTreeMap;
+
+    // check the compression rule, return the number of nodes;
+    fn check<V>(node: &Node<V>, is_root: bool) -> usize {
+        if !is_root {
+            assert!(!node.label.is_empty());
+            assert!(node.val.is_some() || node.children.len() >= 2);
+        }
+        assert!(node
+            .children
+            .windows(2)
+            .all(|w| w[0].label[0] < w[1].label[0]));
+        1 + node.children.iter().map(|c| check(c, false)).sum::<usize>()
+    }
+
+    fn keys<V>(itr: super::PrefixItr<V>) -> Vec<String> {
+        itr.map(|(k, _)| String::from_utf8(k).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_basics() {
+        let mut rt: RadixTree<i32> = RadixTree::new();
+        let words = [
+            "romane", "romanus", "romulus", "rubens", "ruber", "rubicon", "r",
+        ];
+        for (i, w) in words.iter().enumerate() {
+            assert_eq!(rt.insert(*w, i as i32), None);
+        }
+        assert_eq!(rt.len(), 7);
+        //root, r, om, an, e, us, ulus, ub, e, ns, r, icon;
+        assert_eq!(check(&rt.root, true), 12);
+
+        assert_eq!(rt.get("romanus"), Some(&1));
+        assert_eq!(rt.get("roman"), None);
+        assert_eq!(rt.get("romanusx"), None);
+        assert_eq!(rt.get(b"r".as_ref()), Some(&6));
+        assert_eq!(rt.insert("r", 60), Some(6));
+        assert_eq!(rt.len(), 7);
+
+        assert_eq!(
+            keys(rt.iter()),
+            vec!["r", "romane", "romanus", "romulus", "rubens", "ruber", "rubicon"]
+        );
+        assert_eq!(
+            keys(rt.prefix_iter("rom")),
+            vec!["romane", "romanus", "romulus"]
+        );
+        assert_eq!(keys(rt.prefix_iter("rube")), vec!["rubens", "ruber"]);
+        assert_eq!(keys(rt.prefix_iter("ruber")), vec!["ruber"]);
+        assert!(keys(rt.prefix_iter("rubex")).is_empty());
+        assert!(keys(rt.prefix_iter("x")).is_empty());
+
+        assert_eq!(rt.remove("roman"), None);
+        assert_eq!(rt.remove("romane"), Some(0));
+        //"an" and "us" are merged into "anus";
+        assert_eq!(check(&rt.root, true), 10);
+        assert_eq!(rt.remove("romanus"), Some(1));
+        assert_eq!(rt.remove("romulus"), Some(2));
+        assert_eq!(rt.remove("r"), Some(60));
+        assert_eq!(keys(rt.iter()), vec!["rubens", "ruber", "rubicon"]);
+        //root, rub, e, ns, r, icon;
+        assert_eq!(check(&rt.root, true), 6);
+
+        assert_eq!(rt.insert("", -1), None);
+        assert_eq!(keys(rt.iter())[0], "");
+        assert_eq!(rt.remove(""), Some(-1));
+        assert_eq!(rt.len(), 3);
+    }
+
+    #[test]
+    fn test_longest_prefix() {
+        let mut routes: RadixTree<&str> = RadixTree::new();
+        routes.insert("10.", "eth0");
+        routes.insert("10.1.", "eth1");
+        routes.insert("10.1.2.", "eth2");
+        routes.insert("192.168.", "wlan0");
+
+        assert_eq!(routes.longest_prefix("10.1.2.3"), Some((7, &"eth2")));
+        assert_eq!(routes.longest_prefix("10.1.3.3"), Some((5, &"eth1")));
+        assert_eq!(routes.longest_prefix("10.2.2.3"), Some((3, &"eth0")));
+        assert_eq!(routes.longest_prefix("10.1"), Some((3, &"eth0")));
+        assert_eq!(routes.longest_prefix("192.169.0.1"), None);
+
+        routes.insert("", "default");
+        assert_eq!(routes.longest_prefix("192.169.0.1"), Some((0, &"default")));
+    }
+
+    // Randomized differential test against a BTreeMap, whose order on Vec<u8> is lexicographic;
+    #[test]
+    fn test_against_std() {
+        let mut rng = Prng::new(31);
+        let mut rt: RadixTree<usize> = RadixTree::new();
+        let mut std_map: BTreeMap<Vec<u8>, usize> = BTreeMap::new();
+
+        //short keys over a small alphabet share a lot of prefixes;
+        let random_key = |rng: &mut Prng| -> Vec<u8> {
+            let n = rng.gen_range(6);
+            (0..n).map(|_| b"abc"[rng.gen_range(3)]).collect()
+        };
+
+        for round in 0..10000 {
+            let key = random_key(&mut rng);
+            if rng.gen_range(3) == 0 {
+                assert_eq!(rt.remove(&key), std_map.remove(&key));
+            } else {
+                assert_eq!(rt.insert(&key, round), std_map.insert(key, round));
+            }
+            assert_eq!(rt.len(), std_map.len());
+
+            if round % 100 == 0 {
+                check(&rt.root, true);
+                let prefix = random_key(&mut rng);
+                let got: Vec<(Vec<u8>, &usize)> = rt.prefix_iter(&prefix).collect();
+                let expect: Vec<(Vec<u8>, &usize)> = std_map
+                    .range(prefix.clone()..)
+                    .take_while(|(k, _)| k.starts_with(&prefix))
+                    .map(|(k, v)| (k.clone(), v))
+                    .collect();
+                assert_eq!(got, expect);
+
+                let expect = (0..=prefix.len())
+                    .rev()
+                    .find_map(|n| std_map.get(&prefix[..n]).map(|v| (n, v)));
+                assert_eq!(rt.longest_prefix(&prefix), expect);
+            }
+        }
+    }
+}
@@ -0,0 +1,310 @@
+use std::cmp::Ordering;
+
+use super::bst1::{build_balanced, walk, Iter, Link, Node, Order, Visitor};
+
+// A scapegoat tree keeps plain bst1 nodes, no height, color or size in them. Instead, the tree
+// as a whole is kept α-height-balanced: height <= log(n)/log(1/α). When an insert goes deeper
+// than that, some ancestor of the new node must be α-weight-unbalanced (one child holds more
+// than α of its nodes); that ancestor is the "scapegoat", and its subtree is rebuilt into a
+// perfectly balanced one. Removes only count; when enough nodes are gone the whole tree is
+// rebuilt. Both are amortized O(log(n)).
+//
+// See Galperin & Rivest, "Scapegoat trees", 1993.
+pub struct ScapegoatTree<T> {
+    root: Link<T>,
+    size: usize,
+    //the max size since the last rebuild of the whole tree;
+    max_size: usize,
+    alpha: f64,
+}
+
+const DEFAULT_ALPHA: f64 = 2.0 / 3.0;
+
+impl<T: Ord> ScapegoatTree<T> {
+    pub fn new() -> Self {
+        Self::with_alpha(DEFAULT_ALPHA)
+    }
+
+    // α in (0.5, 1): the smaller, the more balanced, and the more often it rebuilds;
+    pub fn with_alpha(alpha: f64) -> Self {
+        assert!(
+            alpha > 0.5 && alpha < 1.0,
+            "ScapegoatTree: alpha must be in (0.5, 1)"
+        );
+        ScapegoatTree {
+            root: None,
+            size: 0,
+            max_size: 0,
+            alpha,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    // Duplicates are allowed, the same as BSTree;
+    pub fn insert(&mut self, val: T) {
+        //the same as BSTree::insert(), but remember the way down: true means left;
+        let mut path: Vec<bool> = Vec::new();
+        let mut target_place: &mut Link<T> = &mut self.root;
+        while let Some(boxed_node) = target_place {
+            let go_left = val <= boxed_node.val;
+            path.push(go_left);
+            target_place = if go_left {
+                &mut boxed_node.left
+            } else {
+                &mut boxed_node.right
+            }
+        }
+        *target_place = Some(Box::new(Node {
+            val,
+            left: None,
+            right: None,
+        }));
+
+        self.size += 1;
+        self.max_size = self.max_size.max(self.size);
+
+        //path.len() is the depth of the new node;
+        if path.len() as f64 > self.height_bound() {
+            if let Some(depth) = self.find_scapegoat(&path) {
+                let link = link_at(&mut self.root, &path[..depth]);
+                rebuild(link);
+            }
+        }
+    }
+
+    pub fn get(&self, val: &T) -> Option<&T> {
+        let mut link: &Link<T> = &self.root;
+        while let Some(boxed_node) = link {
+            link = match val.cmp(&boxed_node.val) {
+                Ordering::Less => &boxed_node.left,
+                Ordering::Greater => &boxed_node.right,
+                Ordering::Equal => return Some(&boxed_node.val),
+            }
+        }
+        None
+    }
+
+    pub fn remove(&mut self, val: &T) -> Option<T> {
+        //find the link pointing to the node; check with a shared ref first, and then re-borrow
+        //mutably, because the borrow checker doesn't allow breaking out of the loop while the
+        //link is mutably borrowed by the `Some(boxed_node)` pattern;
+        let mut link: &mut Link<T> = &mut self.root;
+        loop {
+            let ord = match link.as_ref() {
+                None => return None,
+                Some(boxed_node) => val.cmp(&boxed_node.val),
+            };
+            link = match ord {
+                Ordering::Equal => break,
+                Ordering::Less => &mut link.as_mut().unwrap().left,
+                Ordering::Greater => &mut link.as_mut().unwrap().right,
+            };
+        }
+
+        let mut boxed_node = link.take().unwrap();
+        *link = match (boxed_node.left.take(), boxed_node.right.take()) {
+            (None, right) => right,
+            (left, None) => left,
+            (left, right) => {
+                //two children: the successor (min of the right subtree) takes the place;
+                let mut right = right;
+                let mut succ = take_min(&mut right);
+                succ.left = left;
+                succ.right = right;
+                Some(succ)
+            }
+        };
+
+        self.size -= 1;
+        if (self.size as f64) < self.alpha * self.max_size as f64 {
+            rebuild(&mut self.root);
+            self.max_size = self.size;
+        }
+        Some(boxed_node.val)
+    }
+
+    pub fn iter(&self, order: Order) -> Iter<'_, T> {
+        Iter::new(self.root.as_deref(), order, 0)
+    }
+
+    pub fn walk<V: Visitor<T>>(&self, visitor: &mut V) {
+        walk(self.root.as_deref(), 0, visitor);
+    }
+
+    // log(n)/log(1/α), the max depth allowed;
+    fn height_bound(&self) -> f64 {
+        (self.size as f64).ln() / (1.0 / self.alpha).ln()
+    }
+
+    // Walk up from the new node (at the end of `path`) and return the depth of the first
+    // ancestor with a child heavier than α of its own size. Sizes are not stored, so they are
+    // counted here: going up one level, only the sibling subtree has to be counted, thus the
+    // total cost is bounded by the size of the scapegoat's subtree, which is to be rebuilt
+    // (that's O(size) too) anyway.
+    fn find_scapegoat(&self, path: &[bool]) -> Option<usize> {
+        let mut nodes: Vec<&Node<T>> = Vec::with_capacity(path.len());
+        let mut link: &Link<T> = &self.root;
+        for go_left in path.iter() {
+            let boxed_node = link.as_ref().unwrap();
+            nodes.push(boxed_node);
+            link = if *go_left {
+                &boxed_node.left
+            } else {
+                &boxed_node.right
+            };
+        }
+
+        //size of the subtree on the path below the current node, starting with the new leaf;
+        let mut child_size: usize = 1;
+        for depth in (0..path.len()).rev() {
+            let node = nodes[depth];
+            let sibling = if path[depth] { &node.right } else { &node.left };
+            let size = 1 + child_size + count(sibling);
+            if child_size as f64 > self.alpha * size as f64 {
+                return Some(depth);
+            }
+            child_size = size;
+        }
+        None
+    }
+}
+
+impl<T: Ord> Default for ScapegoatTree<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// follow `path` from `root` and return the link reached;
+fn link_at<'a, T>(root: &'a mut Link<T>, path: &[bool]) -> &'a mut Link<T> {
+    let mut link = root;
+    for go_left in path.iter() {
+        let boxed_node = link.as_mut().unwrap();
+        link = if *go_left {
+            &mut boxed_node.left
+        } else {
+            &mut boxed_node.right
+        };
+    }
+    link
+}
+
+fn count<T>(link: &Link<T>) -> usize {
+    Iter::new(link.as_deref(), Order::Pre, 0).count()
+}
+
+fn take_min<T>(link: &mut Link<T>) -> Box<Node<T>> {
+    let mut link = link;
+    while link.as_ref().unwrap().left.is_some() {
+        link = &mut link.as_mut().unwrap().left;
+    }
+    let mut min = link.take().unwrap();
+    *link = min.right.take();
+    min
+}
+
+// Flatten the subtree into its nodes in order (iteratively, like bst1::Iter does), and rebuild a
+// perfectly balanced one from them; no node is reallocated.
+fn rebuild<T>(link: &mut Link<T>) {
+    let mut nodes: Vec<Box<Node<T>>> = Vec::new();
+    let mut stack: Vec<Box<Node<T>>> = Vec::new();
+    let mut curr = link.take();
+    loop {
+        while let Some(mut boxed_node) = curr {
+            curr = boxed_node.left.take();
+            stack.push(boxed_node);
+        }
+        match stack.pop() {
+            None => break,
+            Some(mut boxed_node) => {
+                curr = boxed_node.right.take();
+                nodes.push(boxed_node);
+            }
+        }
+    }
+
+    let n = nodes.len();
+    *link = build_balanced(&mut nodes.into_iter(), n);
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Link, Order, ScapegoatTree};
+    use crate::prng::Prng;
+
+    fn height<T>(link: &Link<T>) -> usize {
+        match link {
+            None => 0,
+            Some(node) => 1 + height(&node.left).max(height(&node.right)),
+        }
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut sg: ScapegoatTree<i32> = ScapegoatTree::new();
+        //sorted inserts make a list: 1->2->3->4; then 5 lands at depth 4, which is over
+        //log(5)/log(1.5) = 3.97; walking up, 2 is the first node whose child (3, holding 3 of
+        //its 4 nodes) is heavier than 2/3, so 2..=5 is rebuilt to 3(2, 4->5);
+        //then 7 lands at depth 5 > log(7)/log(1.5) = 4.80, and 4..=7 is rebuilt to 5(4, 6->7);
+        for i in 1..=7 {
+            sg.insert(i);
+        }
+        let c: Vec<&i32> = sg.iter(Order::Pre).collect();
+        let expect: Vec<&i32> = vec![&1, &3, &2, &5, &4, &6, &7];
+        assert_eq!(c, expect);
+
+        let c: Vec<&i32> = sg.iter(Order::In).collect();
+        let expect: Vec<&i32> = vec![&1, &2, &3, &4, &5, &6, &7];
+        assert_eq!(c, expect);
+    }
+
+    #[test]
+    fn test_height_bound() {
+        let mut sg: ScapegoatTree<u32> = ScapegoatTree::new();
+        for i in 0..10000 {
+            sg.insert(i);
+            let bound = (sg.len() as f64).ln() / (1.5f64).ln();
+            assert!(height(&sg.root) as f64 <= bound.floor() + 1.0);
+        }
+        assert_eq!(sg.len(), 10000);
+        assert_eq!(sg.get(&9999), Some(&9999));
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut rng = Prng::new(29);
+        let mut sg: ScapegoatTree<usize> = ScapegoatTree::with_alpha(0.75);
+        let mut vals: Vec<usize> = (0..2000).map(|_| rng.gen_range(500)).collect();
+        for v in vals.iter() {
+            sg.insert(*v);
+        }
+
+        rng.shuffle(&mut vals);
+        let (gone, kept) = vals.split_at(1500);
+        for v in gone.iter() {
+            assert_eq!(sg.remove(v), Some(*v));
+        }
+        assert_eq!(sg.remove(&500), None);
+        assert_eq!(sg.len(), 500);
+        let bound = (sg.len() as f64).ln() / (1.0f64 / 0.75).ln();
+        assert!(height(&sg.root) as f64 <= bound.floor() + 1.0);
+
+        let mut kept = kept.to_vec();
+        kept.sort();
+        let c: Vec<usize> = sg.iter(Order::In).cloned().collect();
+        assert_eq!(c, kept);
+
+        for v in kept.iter() {
+            assert_eq!(sg.remove(v), Some(*v));
+        }
+        assert!(sg.is_empty());
+        assert!(sg.root.is_none());
+    }
+}
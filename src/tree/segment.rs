@@ -0,0 +1,345 @@
+use std::marker::PhantomData;
+use std::ops::Range;
+
+// The operation of a segment tree: `combine` must be associative, with `identity` as its
+// identity element (e.g. 0 for sum, MAX for min). A range update adds a "delta" to every element
+// in the range; `apply` tells how a delta changes the aggregate of `len` elements, and
+// `compose` merges two pending deltas, the older one first.
+pub trait Op<T> {
+    fn identity() -> T;
+    fn combine(a: &T, b: &T) -> T;
+    fn apply(agg: &T, delta: &T, len: usize) -> T;
+    fn compose(older: &T, newer: &T) -> T;
+}
+
+// The common ones: sum, min and max, with "add delta to each element" as the range update;
+pub struct Sum;
+pub struct Min;
+pub struct Max;
+
+macro_rules! impl_ops {
+    ($($t:ty, $lowest:expr, $highest:expr);* $(;)?) => {
+        $(
+            impl Op<$t> for Sum {
+                fn identity() -> $t {
+                    0 as $t
+                }
+                fn combine(a: &$t, b: &$t) -> $t {
+                    *a + *b
+                }
+                fn apply(agg: &$t, delta: &$t, len: usize) -> $t {
+                    *agg + *delta * len as $t
+                }
+                fn compose(older: &$t, newer: &$t) -> $t {
+                    *older + *newer
+                }
+            }
+
+            impl Op<$t> for Min {
+                fn identity() -> $t {
+                    $highest
+                }
+                fn combine(a: &$t, b: &$t) -> $t {
+                    if *a < *b { *a } else { *b }
+                }
+                fn apply(agg: &$t, delta: &$t, _len: usize) -> $t {
+                    *agg + *delta
+                }
+                fn compose(older: &$t, newer: &$t) -> $t {
+                    *older + *newer
+                }
+            }
+
+            impl Op<$t> for Max {
+                fn identity() -> $t {
+                    $lowest
+                }
+                fn combine(a: &$t, b: &$t) -> $t {
+                    if *a > *b { *a } else { *b }
+                }
+                fn apply(agg: &$t, delta: &$t, _len: usize) -> $t {
+                    *agg + *delta
+                }
+                fn compose(older: &$t, newer: &$t) -> $t {
+                    *older + *newer
+                }
+            }
+        )*
+    };
+}
+
+impl_ops! {
+    i32, i32::MIN, i32::MAX;
+    i64, i64::MIN, i64::MAX;
+    u32, u32::MIN, u32::MAX;
+    u64, u64::MIN, u64::MAX;
+    usize, usize::MIN, usize::MAX;
+    f64, f64::NEG_INFINITY, f64::INFINITY;
+}
+
+// A segment tree over n elements: node 1 covers [0, n), and node k covering [l, r) has children
+// 2k and 2k+1 covering [l, m) and [m, r), where m = (l+r)/2; so it's an implicit binary tree in
+// an array of 4n slots, just like a binary heap.
+//
+// Every node keeps the aggregate of its segment. A range update stops at the nodes fully inside
+// the range, updates their aggregates and leaves the delta there as a "lazy" tag; the tag is
+// pushed down to the children only when somebody has to go below that node.
+pub struct SegmentTree<T, O> {
+    n: usize,
+    tree: Vec<T>,
+    lazy: Vec<Option<T>>,
+    _op: PhantomData<O>,
+}
+
+impl<T: Clone, O: Op<T>> SegmentTree<T, O> {
+    pub fn new(vals: &[T]) -> Self {
+        let n = vals.len();
+        let mut st = SegmentTree {
+            n,
+            tree: vec![O::identity(); 4 * n.max(1)],
+            lazy: vec![None; 4 * n.max(1)],
+            _op: PhantomData,
+        };
+        if n > 0 {
+            st.build(1, 0, n, vals);
+        }
+        st
+    }
+
+    pub fn len(&self) -> usize {
+        self.n
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    // combine of the elements in `range`; the identity if the range is empty;
+    pub fn query(&self, range: Range<usize>) -> T {
+        self.check_range(&range);
+        if range.start >= range.end {
+            return O::identity();
+        }
+        self.query_node(1, 0, self.n, &range)
+    }
+
+    // set the element at `i` to `val`;
+    pub fn set(&mut self, i: usize, val: T) {
+        assert!(i < self.n, "SegmentTree::set: index out of range");
+        self.set_node(1, 0, self.n, i, val);
+    }
+
+    // add `delta` to every element in `range`, see Op::apply;
+    pub fn range_update(&mut self, range: Range<usize>, delta: T) {
+        self.check_range(&range);
+        if range.start < range.end {
+            self.update_node(1, 0, self.n, &range, &delta);
+        }
+    }
+
+    fn check_range(&self, range: &Range<usize>) {
+        assert!(
+            range.end <= self.n,
+            "SegmentTree: range end {} out of {}",
+            range.end,
+            self.n
+        );
+    }
+
+    fn build(&mut self, k: usize, l: usize, r: usize, vals: &[T]) {
+        if r - l == 1 {
+            self.tree[k] = vals[l].clone();
+            return;
+        }
+        let m = (l + r) / 2;
+        self.build(2 * k, l, m, vals);
+        self.build(2 * k + 1, m, r, vals);
+        self.tree[k] = O::combine(&self.tree[2 * k], &self.tree[2 * k + 1]);
+    }
+
+    // node k covers `len` elements; update it with `delta` as if the whole segment has been
+    // updated, and tag it for the children (a leaf has none);
+    fn tag(&mut self, k: usize, len: usize, delta: &T) {
+        self.tree[k] = O::apply(&self.tree[k], delta, len);
+        if len == 1 {
+            return;
+        }
+        self.lazy[k] = Some(match &self.lazy[k] {
+            None => delta.clone(),
+            Some(older) => O::compose(older, delta),
+        });
+    }
+
+    fn push_down(&mut self, k: usize, l: usize, r: usize) {
+        if let Some(delta) = self.lazy[k].take() {
+            let m = (l + r) / 2;
+            self.tag(2 * k, m - l, &delta);
+            self.tag(2 * k + 1, r - m, &delta);
+        }
+    }
+
+    // `query` doesn't push the tags down (so it only needs `&self`): a tag on node k is pending
+    // for everything below, so apply it to whatever is collected from below, over the length
+    // actually covered;
+    fn query_node(&self, k: usize, l: usize, r: usize, range: &Range<usize>) -> T {
+        if range.start <= l && r <= range.end {
+            return self.tree[k].clone();
+        }
+        let m = (l + r) / 2;
+        let mut agg = O::identity();
+        if range.start < m {
+            agg = O::combine(&agg, &self.query_node(2 * k, l, m, range));
+        }
+        if m < range.end {
+            agg = O::combine(&agg, &self.query_node(2 * k + 1, m, r, range));
+        }
+        if let Some(delta) = &self.lazy[k] {
+            let covered = range.end.min(r) - range.start.max(l);
+            agg = O::apply(&agg, delta, covered);
+        }
+        agg
+    }
+
+    fn set_node(&mut self, k: usize, l: usize, r: usize, i: usize, val: T) {
+        if r - l == 1 {
+            self.tree[k] = val;
+            return;
+        }
+        self.push_down(k, l, r);
+        let m = (l + r) / 2;
+        if i < m {
+            self.set_node(2 * k, l, m, i, val);
+        } else {
+            self.set_node(2 * k + 1, m, r, i, val);
+        }
+        self.tree[k] = O::combine(&self.tree[2 * k], &self.tree[2 * k + 1]);
+    }
+
+    fn update_node(&mut self, k: usize, l: usize, r: usize, range: &Range<usize>, delta: &T) {
+        if range.start <= l && r <= range.end {
+            self.tag(k, r - l, delta);
+            return;
+        }
+        self.push_down(k, l, r);
+        let m = (l + r) / 2;
+        if range.start < m {
+            self.update_node(2 * k, l, m, range, delta);
+        }
+        if m < range.end {
+            self.update_node(2 * k + 1, m, r, range, delta);
+        }
+        self.tree[k] = O::combine(&self.tree[2 * k], &self.tree[2 * k + 1]);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Max, Min, Op, SegmentTree, Sum};
+    use crate::prng::Prng;
+
+    #[test]
+    fn test_basics() {
+        let vals = vec![5, 3, 8, 6, 1, 4, 7, 2];
+        let mut sum: SegmentTree<i64, Sum> = SegmentTree::new(&vals);
+        let mut min: SegmentTree<i64, Min> = SegmentTree::new(&vals);
+        let mut max: SegmentTree<i64, Max> = SegmentTree::new(&vals);
+
+        assert_eq!(sum.query(0..8), 36);
+        assert_eq!(sum.query(2..5), 15);
+        assert_eq!(sum.query(3..3), 0);
+        assert_eq!(min.query(0..4), 3);
+        assert_eq!(max.query(4..8), 7);
+
+        //[5, 3, 8, 16, 11, 14, 7, 2]
+        sum.range_update(3..6, 10);
+        min.range_update(3..6, 10);
+        max.range_update(3..6, 10);
+        assert_eq!(sum.query(0..8), 66);
+        assert_eq!(sum.query(4..5), 11);
+        assert_eq!(sum.query(5..8), 23);
+        assert_eq!(min.query(3..6), 11);
+        assert_eq!(max.query(0..8), 16);
+
+        //[5, 3, 8, 16, 0, 14, 7, 2]
+        sum.set(4, 0);
+        min.set(4, 0);
+        max.set(4, 0);
+        assert_eq!(sum.query(3..6), 30);
+        assert_eq!(min.query(0..8), 0);
+        assert_eq!(max.query(4..6), 14);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_out_of_range() {
+        let st: SegmentTree<i32, Sum> = SegmentTree::new(&[1, 2, 3]);
+        st.query(1..4);
+    }
+
+    // a user defined op: count of elements and sum, for averages; the range update is still
+    // "add delta to each element", and the count part of the delta is ignored;
+    struct Avg;
+
+    impl Op<(u64, f64)> for Avg {
+        fn identity() -> (u64, f64) {
+            (0, 0.0)
+        }
+        fn combine(a: &(u64, f64), b: &(u64, f64)) -> (u64, f64) {
+            (a.0 + b.0, a.1 + b.1)
+        }
+        fn apply(agg: &(u64, f64), delta: &(u64, f64), len: usize) -> (u64, f64) {
+            (agg.0, agg.1 + delta.1 * len as f64)
+        }
+        fn compose(older: &(u64, f64), newer: &(u64, f64)) -> (u64, f64) {
+            (0, older.1 + newer.1)
+        }
+    }
+
+    #[test]
+    fn test_custom_op() {
+        let vals: Vec<(u64, f64)> = vec![(1, 1.0), (1, 2.0), (1, 3.0), (1, 4.0)];
+        let mut st: SegmentTree<(u64, f64), Avg> = SegmentTree::new(&vals);
+        let (n, s) = st.query(1..4);
+        assert_eq!(s / n as f64, 3.0);
+        st.range_update(0..2, (0, 1.0));
+        let (n, s) = st.query(0..4);
+        assert_eq!(s / n as f64, 3.0);
+    }
+
+    // Randomized test against a plain vector;
+    #[test]
+    fn test_random() {
+        let mut rng = Prng::new(32);
+        let n = 100;
+        let mut vals: Vec<i64> = (0..n).map(|_| rng.gen_range(1000) as i64).collect();
+        let mut sum: SegmentTree<i64, Sum> = SegmentTree::new(&vals);
+        let mut min: SegmentTree<i64, Min> = SegmentTree::new(&vals);
+        let mut max: SegmentTree<i64, Max> = SegmentTree::new(&vals);
+
+        for _ in 0..5000 {
+            let a = rng.gen_range(n);
+            let b = a + 1 + rng.gen_range(n - a);
+            match rng.gen_range(3) {
+                0 => {
+                    let v = rng.gen_range(1000) as i64 - 500;
+                    vals[a] = v;
+                    sum.set(a, v);
+                    min.set(a, v);
+                    max.set(a, v);
+                }
+                1 => {
+                    let delta = rng.gen_range(100) as i64 - 50;
+                    vals[a..b].iter_mut().for_each(|v| *v += delta);
+                    sum.range_update(a..b, delta);
+                    min.range_update(a..b, delta);
+                    max.range_update(a..b, delta);
+                }
+                _ => {
+                    assert_eq!(sum.query(a..b), vals[a..b].iter().sum::<i64>());
+                    assert_eq!(min.query(a..b), *vals[a..b].iter().min().unwrap());
+                    assert_eq!(max.query(a..b), *vals[a..b].iter().max().unwrap());
+                }
+            }
+        }
+    }
+}
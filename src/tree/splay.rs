@@ -0,0 +1,362 @@
+use std::cmp::Ordering;
+
+use super::bst1::{walk, Iter, Link, Node, Order, Visitor};
+
+// A splay tree is a plain BST (it shares bst1's Node) which moves every accessed node to the
+// root, so recently used keys are cheap to access again. There's no balance info in the nodes,
+// the tree can even degenerate into a list, but any sequence of m accesses on n nodes costs
+// O((m+n)*log(n)) in total (amortized O(log(n)) per access).
+pub struct SplayTree<T> {
+    root: Link<T>,
+}
+
+impl<T: Ord> SplayTree<T> {
+    pub fn new() -> Self {
+        SplayTree { root: None }
+    }
+
+    // Duplicates are allowed, the same as BSTree: the new node becomes the root, and the old
+    // equal node goes to its left subtree;
+    pub fn insert(&mut self, val: T) {
+        let mut node = Box::new(Node {
+            val,
+            left: None,
+            right: None,
+        });
+
+        //after splaying, root is the last node on the search path of `val`, that is, the
+        //predecessor or the successor of `val`; so we can split the tree at root into two
+        //halves: one <= val and one > val, and hang them under the new node;
+        if let Some(mut root) = splay(self.root.take(), |v| node.val.cmp(v)) {
+            if root.val <= node.val {
+                node.right = root.right.take();
+                node.left = Some(root);
+            } else {
+                node.left = root.left.take();
+                node.right = Some(root);
+            }
+        }
+        self.root = Some(node);
+    }
+
+    // `get` takes `&mut self`, because even a lookup restructures a splay tree;
+    pub fn get(&mut self, val: &T) -> Option<&T> {
+        self.root = splay(self.root.take(), |v| val.cmp(v));
+        match &self.root {
+            Some(boxed_node) if boxed_node.val == *val => Some(&boxed_node.val),
+            _ => None,
+        }
+    }
+
+    pub fn remove(&mut self, val: &T) -> Option<T> {
+        let mut root = splay(self.root.take(), |v| val.cmp(v))?;
+        if root.val != *val {
+            self.root = Some(root);
+            return None;
+        }
+
+        //`val` is at root now; join its two subtrees: splay the max of the left subtree to the
+        //top, it has no right child then, so the right subtree can be hung there;
+        self.root = match root.left.take() {
+            None => root.right.take(),
+            Some(left) => {
+                let mut max = splay(Some(left), |_| Ordering::Greater).unwrap();
+                max.right = root.right.take();
+                Some(max)
+            }
+        };
+        Some(root.val)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn iter(&self, order: Order) -> Iter<'_, T> {
+        Iter::new(self.root.as_deref(), order, 0)
+    }
+
+    pub fn walk<V: Visitor<T>>(&self, visitor: &mut V) {
+        walk(self.root.as_deref(), 0, visitor);
+    }
+}
+
+impl<T: Ord> Default for SplayTree<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// A splay tree can be as deep as a list (e.g. after inserting sorted keys), so the default
+// recursive drop of Box<Node<T>> may overflow the stack; drop it iteratively instead, see
+// linkedlist1::List::drop() for the idea;
+impl<T> Drop for SplayTree<T> {
+    fn drop(&mut self) {
+        let mut stack: Vec<Box<Node<T>>> = Vec::new();
+        if let Some(boxed_node) = self.root.take() {
+            stack.push(boxed_node);
+        }
+        while let Some(mut boxed_node) = stack.pop() {
+            if let Some(left) = boxed_node.left.take() {
+                stack.push(left);
+            }
+            if let Some(right) = boxed_node.right.take() {
+                stack.push(right);
+            }
+            //`boxed_node` has no children now and is dropped here;
+        }
+    }
+}
+
+// Top-down splay: walk down from the root looking for the target (`cmp` tells where the target
+// is relative to a value: Less means go left), and move the node where the search stops to
+// the root. Nodes passed by are split into two trees:
+//     `lefts`:  nodes less than the target, each one will be the right child of the previous;
+//     `rights`: nodes greater than the target, each one will be the left child of the previous;
+// and are reassembled under the new root at last.
+//
+//     zig:     the target is a child of root: link root into `rights` (or `lefts`);
+//     zig-zig: the target is on the left of the left child (or right of the right): rotate first,
+//              then link;
+//     zig-zag: the target is on the right of the left child (or left of the right): link root
+//              into `rights` and then the child into `lefts` in the next round; this is the
+//              "simplified" zig-zag, the result is the same, see Sleator & Tarjan 1985;
+//
+// The recursion-free form avoids stack overflow on degenerated trees.
+fn splay<T, F>(link: Link<T>, cmp: F) -> Link<T>
+where
+    F: Fn(&T) -> Ordering,
+{
+    let mut root = link?;
+    let mut lefts: Vec<Box<Node<T>>> = Vec::new();
+    let mut rights: Vec<Box<Node<T>>> = Vec::new();
+
+    loop {
+        match cmp(&root.val) {
+            Ordering::Equal => break,
+            Ordering::Less => {
+                let mut child = match root.left.take() {
+                    Some(child) => child,
+                    None => break,
+                };
+                if cmp(&child.val) == Ordering::Less {
+                    //zig-zig: rotate right;
+                    root.left = child.right.take();
+                    child.right = Some(root);
+                    root = child;
+                    child = match root.left.take() {
+                        Some(child) => child,
+                        None => break,
+                    };
+                }
+                rights.push(root);
+                root = child;
+            }
+            Ordering::Greater => {
+                let mut child = match root.right.take() {
+                    Some(child) => child,
+                    None => break,
+                };
+                if cmp(&child.val) == Ordering::Greater {
+                    //zag-zag: rotate left;
+                    root.right = child.left.take();
+                    child.left = Some(root);
+                    root = child;
+                    child = match root.right.take() {
+                        Some(child) => child,
+                        None => break,
+                    };
+                }
+                lefts.push(root);
+                root = child;
+            }
+        }
+    }
+
+    //reassemble: the last node in `lefts` gets root's left subtree as its right child, and so
+    //on up to the first one, which becomes root's new left child; same for `rights`;
+    let mut left = root.left.take();
+    while let Some(mut boxed_node) = lefts.pop() {
+        boxed_node.right = left;
+        left = Some(boxed_node);
+    }
+    let mut right = root.right.take();
+    while let Some(mut boxed_node) = rights.pop() {
+        boxed_node.left = right;
+        right = Some(boxed_node);
+    }
+    root.left = left;
+    root.right = right;
+    Some(root)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Order, SplayTree};
+    use crate::prng::Prng;
+    use crate::tree::bst1::BSTree;
+    use std::cell::Cell;
+    use std::cmp::Ordering;
+
+    #[test]
+    fn test_insert_get() {
+        let mut st: SplayTree<i32> = SplayTree::new();
+        let insert_order = vec![8, 4, 10, 6, 5, 7, 9, 12, 13];
+        for val in insert_order.iter() {
+            st.insert(*val);
+            //the inserted node is always moved to root;
+            assert_eq!(st.root.as_ref().map(|n| n.val), Some(*val));
+        }
+
+        let c: Vec<&i32> = st.iter(Order::In).collect();
+        assert_eq!(c, vec![&4, &5, &6, &7, &8, &9, &10, &12, &13]);
+
+        assert_eq!(st.get(&6), Some(&6));
+        assert_eq!(st.iter(Order::Pre).next(), Some(&6));
+        assert_eq!(st.get(&11), None);
+        //a miss splays the last node on the search path;
+        let top = *st.iter(Order::Pre).next().unwrap();
+        assert!(top == 10 || top == 12);
+
+        let c: Vec<&i32> = st.iter(Order::In).collect();
+        assert_eq!(c, vec![&4, &5, &6, &7, &8, &9, &10, &12, &13]);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut st: SplayTree<i32> = SplayTree::new();
+        for val in [5, 3, 8, 3, 1, 9, 7].iter() {
+            st.insert(*val);
+        }
+
+        assert_eq!(st.remove(&4), None);
+        assert_eq!(st.remove(&3), Some(3));
+        assert_eq!(st.remove(&3), Some(3));
+        assert_eq!(st.remove(&3), None);
+        assert_eq!(st.remove(&9), Some(9));
+        assert_eq!(st.remove(&1), Some(1));
+
+        let c: Vec<&i32> = st.iter(Order::In).collect();
+        assert_eq!(c, vec![&5, &7, &8]);
+
+        assert_eq!(st.remove(&5), Some(5));
+        assert_eq!(st.remove(&7), Some(7));
+        assert_eq!(st.remove(&8), Some(8));
+        assert!(st.is_empty());
+        assert_eq!(st.remove(&8), None);
+    }
+
+    #[test]
+    fn test_degenerated() {
+        //sorted inserts make a list-like tree; the first access at the deep end must neither
+        //overflow the stack, nor does the drop at the end;
+        let mut st: SplayTree<u32> = SplayTree::new();
+        for i in 0..200_000 {
+            st.insert(i);
+        }
+        assert_eq!(st.get(&0), Some(&0));
+        assert_eq!(st.get(&100_000), Some(&100_000));
+        assert_eq!(st.iter(Order::In).count(), 200_000);
+    }
+
+    thread_local! {
+        static COMPARISONS: Cell<u64> = const { Cell::new(0) };
+    }
+
+    // a key which counts how many times it's compared;
+    #[derive(Clone, Copy)]
+    struct Key(u32);
+
+    impl PartialEq for Key {
+        fn eq(&self, other: &Self) -> bool {
+            self.cmp(other) == Ordering::Equal
+        }
+    }
+
+    impl Eq for Key {}
+
+    impl PartialOrd for Key {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Key {
+        fn cmp(&self, other: &Self) -> Ordering {
+            COMPARISONS.with(|c| c.set(c.get() + 1));
+            self.0.cmp(&other.0)
+        }
+    }
+
+    fn comparisons() -> u64 {
+        COMPARISONS.with(|c| c.replace(0))
+    }
+
+    // Zipf distribution over ranks [0, n): P(k) is proportional to 1/(k+1)^s;
+    fn zipf_cdf(n: usize, s: f64) -> Vec<f64> {
+        let mut cdf = Vec::with_capacity(n);
+        let mut sum = 0.0;
+        for k in 0..n {
+            sum += 1.0 / ((k + 1) as f64).powf(s);
+            cdf.push(sum);
+        }
+        for p in cdf.iter_mut() {
+            *p /= sum;
+        }
+        cdf
+    }
+
+    fn zipf_sample(cdf: &[f64], rng: &mut Prng) -> usize {
+        let u = rng.next_f64();
+        match cdf.binary_search_by(|p| p.partial_cmp(&u).unwrap()) {
+            Ok(i) | Err(i) => i.min(cdf.len() - 1),
+        }
+    }
+
+    #[test]
+    fn test_zipf_amortized() {
+        const N: usize = 10_000;
+        const ACCESSES: usize = 100_000;
+
+        let mut rng = Prng::new(2020);
+
+        //both trees are built from the same random insert order, that's the good case for the
+        //plain BST (expected depth about 2*ln(n));
+        let mut keys: Vec<u32> = (0..N as u32).collect();
+        rng.shuffle(&mut keys);
+
+        let mut bst: BSTree<Key> = BSTree::new();
+        let mut st: SplayTree<Key> = SplayTree::new();
+        for k in keys.iter() {
+            bst.insert(Key(*k));
+            st.insert(Key(*k));
+        }
+
+        //rank -> key mapping, so that the hot keys are scattered over the key space;
+        rng.shuffle(&mut keys);
+        let cdf = zipf_cdf(N, 1.2);
+        let trace: Vec<Key> = (0..ACCESSES)
+            .map(|_| Key(keys[zipf_sample(&cdf, &mut rng)]))
+            .collect();
+
+        comparisons();
+        for k in trace.iter() {
+            assert!(bst.get(k).is_some());
+        }
+        let bst_cost = comparisons();
+
+        for k in trace.iter() {
+            assert!(st.get(k).is_some());
+        }
+        let splay_cost = comparisons();
+
+        println!(
+            "zipf trace of {} accesses over {} keys: bst {:.2} cmp/access, splay {:.2} cmp/access",
+            ACCESSES,
+            N,
+            bst_cost as f64 / ACCESSES as f64,
+            splay_cost as f64 / ACCESSES as f64
+        );
+        assert!(splay_cost < bst_cost);
+    }
+}
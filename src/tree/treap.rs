@@ -0,0 +1,430 @@
+use std::cmp::Ordering;
+
+use super::bst1::{walk, BinaryNode, Iter, Order, Visitor};
+use crate::prng::Prng;
+
+// Treap = tree + heap: a BST by `val`, and at the same time a max-heap by `priority`. The
+// priorities are random, so the shape is the same as a BST built by inserting the values in a
+// random order, which is balanced with high probability (expected depth O(log(n))), no matter
+// in which order the values are actually inserted.
+//
+// Its nodes aren't bst1's, because of the priority; they implement BinaryNode instead, which is
+// all bst1's traversals need.
+pub struct Node<T> {
+    val: T,
+    priority: u64,
+    left: Link<T>,
+    right: Link<T>,
+}
+
+type Link<T> = Option<Box<Node<T>>>;
+
+pub struct Treap<T> {
+    root: Link<T>,
+    rng: Prng,
+}
+
+const DEFAULT_SEED: u64 = 0x7265_6170;
+
+impl<T: Ord> Treap<T> {
+    // the same seed gives the same priorities, thus the same shape; see `with_seed`;
+    pub fn new() -> Self {
+        Self::with_seed(DEFAULT_SEED)
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        Treap {
+            root: None,
+            rng: Prng::new(seed),
+        }
+    }
+
+    // Duplicates are allowed, the same as BSTree;
+    pub fn insert(&mut self, val: T) {
+        let priority = self.rng.next_u64();
+        self.insert_with_priority(val, priority);
+    }
+
+    fn insert_with_priority(&mut self, val: T, priority: u64) {
+        let node = Box::new(Node {
+            val,
+            priority,
+            left: None,
+            right: None,
+        });
+        self.root = Some(insert(self.root.take(), node));
+    }
+
+    pub fn get(&self, val: &T) -> Option<&T> {
+        let mut link: &Link<T> = &self.root;
+        while let Some(boxed_node) = link {
+            link = match val.cmp(&boxed_node.val) {
+                Ordering::Less => &boxed_node.left,
+                Ordering::Greater => &boxed_node.right,
+                Ordering::Equal => return Some(&boxed_node.val),
+            }
+        }
+        None
+    }
+
+    pub fn remove(&mut self, val: &T) -> Option<T> {
+        remove(&mut self.root, val)
+    }
+
+    // Split the treap into two: the left one holds the values < `key`, and the right one holds
+    // the values >= `key`. The right one gets a new rng seeded from the left one's, so the whole
+    // thing is still reproducible;
+    pub fn split(mut self, key: &T) -> (Treap<T>, Treap<T>) {
+        let (left, right) = split(self.root.take(), key);
+        let seed = self.rng.next_u64();
+        let right = Treap {
+            root: right,
+            rng: Prng::new(seed),
+        };
+        self.root = left;
+        (self, right)
+    }
+
+    // The reverse of `split`: every value in `left` must be <= every value in `right`, otherwise
+    // the result would not be a BST, so we panic. The result keeps the rng of `left`;
+    pub fn merge(mut left: Treap<T>, mut right: Treap<T>) -> Treap<T> {
+        if let (Some(max), Some(min)) = (left.max(), right.min()) {
+            assert!(max <= min, "Treap::merge: left and right overlap");
+        }
+        left.root = merge(left.root.take(), right.root.take());
+        left
+    }
+
+    pub fn min(&self) -> Option<&T> {
+        let mut node = self.root.as_ref()?;
+        while let Some(left) = &node.left {
+            node = left;
+        }
+        Some(&node.val)
+    }
+
+    pub fn max(&self) -> Option<&T> {
+        let mut node = self.root.as_ref()?;
+        while let Some(right) = &node.right {
+            node = right;
+        }
+        Some(&node.val)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn iter(&self, order: Order) -> Iter<'_, T, Node<T>> {
+        Iter::new(self.root.as_deref(), order, 0)
+    }
+
+    pub fn walk<V: Visitor<T>>(&self, visitor: &mut V) {
+        walk(self.root.as_deref(), 0, visitor);
+    }
+}
+
+impl<T: Ord> Default for Treap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//       node             left
+//       /  \             /  \
+//     left  c    =>     a   node
+//     /  \                  /  \
+//    a    b                b    c
+fn rotate_right<T>(mut node: Box<Node<T>>) -> Box<Node<T>> {
+    let mut left = node.left.take().unwrap();
+    node.left = left.right.take();
+    left.right = Some(node);
+    left
+}
+
+//     node                  right
+//     /  \                  /  \
+//    a   right     =>    node   c
+//        /  \            /  \
+//       b    c          a    b
+fn rotate_left<T>(mut node: Box<Node<T>>) -> Box<Node<T>> {
+    let mut right = node.right.take().unwrap();
+    node.right = right.left.take();
+    right.left = Some(node);
+    right
+}
+
+// Insert as a leaf like a plain BST, then rotate it up on the way back while its priority is
+// greater than its parent's;
+fn insert<T: Ord>(link: Link<T>, new_node: Box<Node<T>>) -> Box<Node<T>> {
+    match link {
+        None => new_node,
+        Some(mut node) => {
+            if new_node.val <= node.val {
+                let left = insert(node.left.take(), new_node);
+                let rotate = left.priority > node.priority;
+                node.left = Some(left);
+                if rotate {
+                    node = rotate_right(node);
+                }
+            } else {
+                let right = insert(node.right.take(), new_node);
+                let rotate = right.priority > node.priority;
+                node.right = Some(right);
+                if rotate {
+                    node = rotate_left(node);
+                }
+            }
+            node
+        }
+    }
+}
+
+fn remove<T: Ord>(link: &mut Link<T>, val: &T) -> Option<T> {
+    let node = link.as_mut()?;
+    match val.cmp(&node.val) {
+        Ordering::Less => remove(&mut node.left, val),
+        Ordering::Greater => remove(&mut node.right, val),
+        Ordering::Equal => {
+            let (rest, val) = remove_root(link.take().unwrap());
+            *link = rest;
+            Some(val)
+        }
+    }
+}
+
+// Rotate `node` down, always lifting the child with the greater priority so that the heap
+// property holds, until it has at most one child; then it can be unlinked directly;
+fn remove_root<T>(node: Box<Node<T>>) -> (Link<T>, T) {
+    let lift_left = match (&node.left, &node.right) {
+        (None, _) | (_, None) => {
+            let Node {
+                val, left, right, ..
+            } = *node;
+            return (left.or(right), val);
+        }
+        (Some(left), Some(right)) => left.priority > right.priority,
+    };
+
+    let mut top;
+    let val;
+    if lift_left {
+        top = rotate_right(node);
+        let (rest, v) = remove_root(top.right.take().unwrap());
+        top.right = rest;
+        val = v;
+    } else {
+        top = rotate_left(node);
+        let (rest, v) = remove_root(top.left.take().unwrap());
+        top.left = rest;
+        val = v;
+    }
+    (Some(top), val)
+}
+
+fn split<T: Ord>(link: Link<T>, key: &T) -> (Link<T>, Link<T>) {
+    match link {
+        None => (None, None),
+        Some(mut node) => {
+            if node.val < *key {
+                //node and its left subtree go to the left; split its right subtree;
+                let (left, right) = split(node.right.take(), key);
+                node.right = left;
+                (Some(node), right)
+            } else {
+                let (left, right) = split(node.left.take(), key);
+                node.left = right;
+                (left, Some(node))
+            }
+        }
+    }
+}
+
+// the root with the greater priority stays at top, and the other tree is merged into its inner
+// side;
+fn merge<T>(left: Link<T>, right: Link<T>) -> Link<T> {
+    match (left, right) {
+        (None, right) => right,
+        (left, None) => left,
+        (Some(mut left), Some(mut right)) => {
+            if left.priority > right.priority {
+                left.right = merge(left.right.take(), Some(right));
+                Some(left)
+            } else {
+                right.left = merge(Some(left), right.left.take());
+                Some(right)
+            }
+        }
+    }
+}
+
+impl<T> BinaryNode for Node<T> {
+    type Val = T;
+
+    fn val(&self) -> &T {
+        &self.val
+    }
+
+    fn left(&self) -> Option<&Self> {
+        self.left.as_deref()
+    }
+
+    fn right(&self) -> Option<&Self> {
+        self.right.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Link, Order, Treap, Visitor};
+
+    // check both the BST property and the heap property; return the number of nodes; note that
+    // rotations may move equal values to either side, so both bounds are inclusive;
+    fn check<T: Ord>(link: &Link<T>, lo: Option<&T>, hi: Option<&T>, max_prio: u64) -> usize {
+        match link {
+            None => 0,
+            Some(node) => {
+                assert!(node.priority <= max_prio);
+                if let Some(lo) = lo {
+                    assert!(*lo <= node.val);
+                }
+                if let Some(hi) = hi {
+                    assert!(node.val <= *hi);
+                }
+                1 + check(&node.left, lo, Some(&node.val), node.priority)
+                    + check(&node.right, Some(&node.val), hi, node.priority)
+            }
+        }
+    }
+
+    fn height<T>(link: &Link<T>) -> usize {
+        match link {
+            None => 0,
+            Some(node) => 1 + height(&node.left).max(height(&node.right)),
+        }
+    }
+
+    #[test]
+    pub fn test_shape() {
+        //with fixed priorities the shape is determined: the max priority is the root, and so on;
+        let mut treap: Treap<i32> = Treap::new();
+        let insert_order = [(8, 90), (4, 70), (10, 80), (6, 95), (5, 10), (7, 60), (9, 20)];
+        for (val, prio) in insert_order.iter() {
+            treap.insert_with_priority(*val, *prio);
+        }
+        check(&treap.root, None, None, u64::MAX);
+
+        let c: Vec<&i32> = treap.iter(Order::Pre).collect();
+        let expect: Vec<&i32> = vec![&6, &4, &5, &8, &7, &10, &9];
+        assert_eq!(c, expect);
+
+        let c: Vec<&i32> = treap.iter(Order::In).collect();
+        let expect: Vec<&i32> = vec![&4, &5, &6, &7, &8, &9, &10];
+        assert_eq!(c, expect);
+
+        let c: Vec<&i32> = treap.iter(Order::Post).collect();
+        let expect: Vec<&i32> = vec![&5, &4, &7, &9, &10, &8, &6];
+        assert_eq!(c, expect);
+
+        let c: Vec<&i32> = treap.iter(Order::Level).collect();
+        let expect: Vec<&i32> = vec![&6, &4, &8, &5, &7, &10, &9];
+        assert_eq!(c, expect);
+
+        //the walker sees the same shape: 9 is the deepest, on level 3;
+        struct Deepest(usize, i32);
+        impl Visitor<i32> for Deepest {
+            fn enter(&mut self, val: &i32, depth: usize) {
+                if depth > self.0 {
+                    *self = Deepest(depth, *val);
+                }
+            }
+        }
+        let mut deepest = Deepest(0, 0);
+        treap.walk(&mut deepest);
+        assert_eq!((deepest.0, deepest.1), (3, 9));
+
+        //6 is rotated down, lifting the child with the greater priority each time: 8, 4, 7;
+        assert_eq!(treap.remove(&6), Some(6));
+        check(&treap.root, None, None, u64::MAX);
+        let c: Vec<&i32> = treap.iter(Order::Pre).collect();
+        let expect: Vec<&i32> = vec![&8, &4, &7, &5, &10, &9];
+        assert_eq!(c, expect);
+    }
+
+    #[test]
+    pub fn test_insert_remove() {
+        let mut treap: Treap<u32> = Treap::with_seed(7);
+        //sorted input would make a plain BST a list;
+        for i in 0..1000 {
+            treap.insert(i);
+        }
+        assert_eq!(check(&treap.root, None, None, u64::MAX), 1000);
+        assert!(height(&treap.root) < 40);
+
+        assert_eq!(treap.get(&500), Some(&500));
+        assert_eq!(treap.get(&1000), None);
+        assert_eq!(treap.min(), Some(&0));
+        assert_eq!(treap.max(), Some(&999));
+
+        for i in (0..1000).step_by(2) {
+            assert_eq!(treap.remove(&i), Some(i));
+        }
+        assert_eq!(treap.remove(&0), None);
+        assert_eq!(check(&treap.root, None, None, u64::MAX), 500);
+
+        let c: Vec<u32> = treap.iter(Order::In).cloned().collect();
+        let expect: Vec<u32> = (1..1000).step_by(2).collect();
+        assert_eq!(c, expect);
+    }
+
+    #[test]
+    pub fn test_reproducible() {
+        let mut t1: Treap<i32> = Treap::with_seed(42);
+        let mut t2: Treap<i32> = Treap::with_seed(42);
+        for i in 0..100 {
+            t1.insert(i);
+            t2.insert(i);
+        }
+        let c1: Vec<&i32> = t1.iter(Order::Pre).collect();
+        let c2: Vec<&i32> = t2.iter(Order::Pre).collect();
+        assert_eq!(c1, c2);
+    }
+
+    #[test]
+    pub fn test_split_merge() {
+        let mut treap: Treap<i32> = Treap::with_seed(1);
+        for val in [5, 1, 9, 3, 7, 5, 2, 8].iter() {
+            treap.insert(*val);
+        }
+
+        let (left, right) = treap.split(&5);
+        check(&left.root, None, None, u64::MAX);
+        check(&right.root, None, None, u64::MAX);
+        let c: Vec<&i32> = left.iter(Order::In).collect();
+        assert_eq!(c, vec![&1, &2, &3]);
+        let c: Vec<&i32> = right.iter(Order::In).collect();
+        assert_eq!(c, vec![&5, &5, &7, &8, &9]);
+
+        let merged = Treap::merge(left, right);
+        assert_eq!(check(&merged.root, None, None, u64::MAX), 8);
+        let c: Vec<&i32> = merged.iter(Order::In).collect();
+        assert_eq!(c, vec![&1, &2, &3, &5, &5, &7, &8, &9]);
+
+        //split at either end;
+        let (left, right) = merged.split(&0);
+        assert!(left.is_empty());
+        let (left, right) = right.split(&100);
+        assert!(right.is_empty());
+        assert_eq!(left.iter(Order::In).count(), 8);
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn test_merge_overlap() {
+        let mut left: Treap<i32> = Treap::new();
+        let mut right: Treap<i32> = Treap::new();
+        left.insert(5);
+        right.insert(3);
+        Treap::merge(left, right);
+    }
+}